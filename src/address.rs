@@ -9,6 +9,12 @@ impl Address {
     }
 }
 
+impl From<u32> for Address {
+    fn from(x: u32) -> Self {
+        Self(x as u64)
+    }
+}
+
 impl From<u64> for Address {
     fn from(x: u64) -> Self {
         Self(x)
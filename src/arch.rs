@@ -0,0 +1,178 @@
+use crate::Base;
+use alloc::string::{String, ToString};
+use core::fmt;
+
+/// The canonical order of the single-letter extensions inside an ISA string.
+const CANONICAL_ORDER: &str = "IEMAFDQLCBKJTPVNH";
+
+/// The supported multi-letter extensions, in the order they appear in an ISA string.
+///
+/// They are ordered by the category given by their second letter, using the canonical
+/// order of the single-letter extensions, and then alphabetically.
+const MULTI_LETTER_EXTENSIONS: [&str; 8] = [
+    "zifencei",
+    "zihintpause",
+    "zacas",
+    "zawrs",
+    "zbkb",
+    "zknd",
+    "zkne",
+    "zknh",
+];
+
+/// The bits in `misa` that indicate supported privilege modes instead of extensions,
+/// and thus are not part of the ISA string.
+const PRIVILEGE_MODES: &str = "SU";
+
+/// Description of the features a RISC-V CPU supports, i.e. its base ISA and the
+/// set of enabled single- and multi-letter extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Architecture {
+    base: Base,
+    /// Bitmask of the enabled extensions, using the same layout as the `misa` CSR.
+    extensions: u32,
+    /// Bitmask of the enabled multi-letter extensions, indexed like `MULTI_LETTER_EXTENSIONS`.
+    multi_letter: u32,
+}
+
+impl Architecture {
+    /// Create a new architecture that only supports the given base ISA.
+    pub fn new(base: Base) -> Self {
+        Self {
+            base,
+            extensions: ext_bit(base.letter()),
+            multi_letter: 0,
+        }
+    }
+
+    /// Create a new architecture with the given base ISA and all the extensions in `exts` enabled.
+    ///
+    /// # Panics
+    ///
+    /// If any of the characters is not a valid extension letter.
+    pub fn from_extensions(base: Base, exts: impl IntoIterator<Item = char>) -> Self {
+        exts.into_iter()
+            .fold(Self::new(base), |arch, ext| arch.with_extension(ext))
+    }
+
+    /// Enable the given single-letter extension.
+    ///
+    /// # Panics
+    ///
+    /// If `ext` is not a letter.
+    pub fn with_extension(mut self, ext: char) -> Self {
+        assert!(ext.is_ascii_alphabetic(), "invalid extension: {:?}", ext);
+        self.extensions |= ext_bit(ext);
+        self
+    }
+
+    /// Enable the given multi-letter extension, e.g. `"Zacas"`. The case of `name` is ignored.
+    ///
+    /// # Panics
+    ///
+    /// If `name` is not one of the supported multi-letter extensions.
+    pub fn with_multi_letter_extension(mut self, name: &str) -> Self {
+        let idx =
+            multi_letter_index(name).unwrap_or_else(|| panic!("unsupported extension: {:?}", name));
+        self.multi_letter |= 1 << idx;
+        self
+    }
+
+    /// Return the base ISA of this architecture.
+    pub fn base(&self) -> Base {
+        self.base
+    }
+
+    /// Return the native register width in bits.
+    pub fn xlen(&self) -> u32 {
+        self.base.xlen()
+    }
+
+    /// Check if the given single-letter extension is supported. The case of `ext` is ignored.
+    pub fn has_extension(&self, ext: char) -> bool {
+        ext.is_ascii_alphabetic() && self.extensions & ext_bit(ext) != 0
+    }
+
+    /// Check if the given multi-letter extension is supported. The case of `name` is ignored.
+    pub fn has_multi_letter_extension(&self, name: &str) -> bool {
+        matches!(multi_letter_index(name), Some(idx) if self.multi_letter & (1 << idx) != 0)
+    }
+
+    /// Return the ISA string of this architecture, e.g. `rv32imac_zacas`.
+    ///
+    /// The supported privilege modes (`S` and `U`) are not part of the ISA string, and
+    /// multi-letter extensions are not part of `misa`.
+    pub fn isa_string(&self) -> String {
+        self.to_string()
+    }
+
+    /// Return the value of the `misa` CSR that describes this architecture.
+    pub fn misa(&self) -> u64 {
+        let mxl: u64 = match self.xlen() {
+            32 => 1,
+            64 => 2,
+            _ => 3,
+        };
+        (mxl << (self.xlen() - 2)) | u64::from(self.extensions)
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rv{}", self.xlen())?;
+
+        // first the extensions that have a canonical order, then the rest alphabetically
+        let ordered = CANONICAL_ORDER.chars();
+        let rest = ('A'..='Z')
+            .filter(|&ext| !CANONICAL_ORDER.contains(ext) && !PRIVILEGE_MODES.contains(ext));
+        for ext in ordered.chain(rest).filter(|&ext| self.has_extension(ext)) {
+            write!(f, "{}", ext.to_ascii_lowercase())?;
+        }
+
+        for (idx, name) in MULTI_LETTER_EXTENSIONS.iter().enumerate() {
+            if self.multi_letter & (1 << idx) != 0 {
+                write!(f, "_{}", name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn multi_letter_index(name: &str) -> Option<usize> {
+    MULTI_LETTER_EXTENSIONS
+        .iter()
+        .position(|ext| ext.eq_ignore_ascii_case(name))
+}
+
+fn ext_bit(ext: char) -> u32 {
+    1 << (ext.to_ascii_uppercase() as u32 - 'A' as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isa_string_and_misa() {
+        let arch = Architecture::from_extensions(Base::RV32I, "cmsua".chars());
+
+        assert!(arch.has_extension('I'));
+        assert!(arch.has_extension('m'));
+        assert!(!arch.has_extension('F'));
+        assert!(!arch.has_extension('1'));
+        assert_eq!(arch.xlen(), 32);
+        assert_eq!(arch.isa_string(), "rv32imac");
+        assert_eq!(arch.misa(), 0x4014_1105);
+
+        let arch = arch
+            .with_multi_letter_extension("Zawrs")
+            .with_multi_letter_extension("zifencei")
+            .with_multi_letter_extension("Zacas");
+        assert!(arch.has_multi_letter_extension("zacas"));
+        assert!(!arch.has_multi_letter_extension("Zbkb"));
+        assert!(!arch.has_multi_letter_extension("Zfoo"));
+        assert_eq!(arch.isa_string(), "rv32imac_zifencei_zacas_zawrs");
+        assert_eq!(arch.misa(), 0x4014_1105);
+    }
+}
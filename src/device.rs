@@ -4,14 +4,46 @@
 mod ram;
 pub use ram::RamDevice;
 
+mod sparse;
+pub use sparse::SparseRamDevice;
+
+mod mock;
+pub use mock::{MockDevice, MockHandle};
+
+mod spi;
+pub use spi::{SpiController, SpiDevice};
+
+mod sdcard;
+pub use sdcard::SdCard;
+
+mod gpio;
+pub use gpio::{GpioController, GpioHandle};
+
+mod shmem;
+pub use shmem::{SharedMemory, SharedMemoryHandle};
+
+#[cfg(feature = "std")]
+pub mod remote;
+#[cfg(feature = "std")]
+pub use remote::RemoteDevice;
+
+mod loader;
+pub use loader::LoadError;
+
 use crate::{
     trap::{Exception, Result},
     Address,
 };
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
 use bytemuck::Pod;
-use object::{File, Object, ObjectSegment};
-use std::collections::HashMap;
-use std::mem::align_of;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::mem::{align_of, size_of};
+use core::ops::Range;
 
 /// The default memory size that each device bus will allocate by default.
 pub const DEFAULT_MEMORY_SIZE: usize = 2 << 20;
@@ -19,6 +51,44 @@ pub const DEFAULT_MEMORY_SIZE: usize = 2 << 20;
 /// The address where DRAM will start.
 pub const DRAM_BASE: u64 = 0x8000_0000;
 
+/// The number of bytes in a single page of the RAM devices.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// A single page of memory.
+type Page = [u8; PAGE_SIZE as usize];
+
+/// Split the access at `off` with `len` bytes into chunks that do not cross a page boundary.
+///
+/// Each chunk is a tuple of the page number, the offset inside the page,
+/// and the range inside the buffer.
+fn page_chunks(off: u64, len: usize) -> impl Iterator<Item = (u64, usize, Range<usize>)> {
+    let mut done = 0;
+    core::iter::from_fn(move || {
+        if done >= len {
+            return None;
+        }
+
+        let addr = off + done as u64;
+        let page_off = (addr % PAGE_SIZE) as usize;
+        let count = (PAGE_SIZE as usize - page_off).min(len - done);
+
+        let chunk = (addr / PAGE_SIZE, page_off, done..done + count);
+        done += count;
+        Some(chunk)
+    })
+}
+
+/// Return the page number and the range inside the page, if the `len` bytes at `off`
+/// are inside a single page.
+fn single_page(off: u64, len: usize) -> Option<(u64, Range<usize>)> {
+    let page_off = (off % PAGE_SIZE) as usize;
+    if page_off + len <= PAGE_SIZE as usize {
+        Some((off / PAGE_SIZE, page_off..page_off + len))
+    } else {
+        None
+    }
+}
+
 /// Any device that is able to read/write memory from/to.
 ///
 /// Any device must specify the size it covers using the `size()` method, but it can not control
@@ -27,6 +97,11 @@ pub trait Device {
     /// The number of bytes this memory device covers, starting from the base address.
     fn size(&self) -> u64;
 
+    /// A short, human readable name describing what kind of device this is, e.g. `"ram"`.
+    fn kind(&self) -> &'static str {
+        "unknown"
+    }
+
     /// Fill `buf` with bytes at the given address.
     ///
     /// Note that the address is a relativ offset to the base address of this device.
@@ -35,24 +110,205 @@ pub trait Device {
     ///
     /// `Ok(())` if the load was successful and the **whole** buffer is filled. Not filling the buffer,
     /// but returning `Ok(())`, will be the same behaviour as filling the buffer with zeros.
+    /// Access faults should carry `off`, which the [`DeviceBus`] replaces with the full address.
     fn load(&self, off: u64, buf: &mut [u8]) -> Result<()>;
 
     /// Write `buf`s contents to the given address.
     ///
     /// Note that the address is a relativ offset to the base address of this device.
+    /// The [`DeviceBus`] only forwards accesses that are fully contained in the device,
+    /// so a device must only reject accesses it can not handle before writing anything.
     ///
     /// # Returns
     ///
     /// `Ok(())` if the write was successful and the **whole** buffer was written.
     /// Not writing the whole buffer, might lead to logic bugs.
     fn write(&mut self, off: u64, buf: &[u8]) -> Result<()>;
+
+    /// Create an independent copy of this device, including its current state.
+    ///
+    /// Devices should make this as cheap as possible, e.g. by sharing memory until it is
+    /// written to. Returns `None` if this device can not be forked, which is the default.
+    fn fork(&self) -> Option<Box<dyn Device>> {
+        None
+    }
+
+    /// Return the `len` bytes at `off` as a slice directly into the memory of this device.
+    ///
+    /// Returns `None` if the range is not backed by contiguous memory, which is the default.
+    /// The [`DeviceBus`] only asks for ranges that are fully contained in the device.
+    fn slice(&self, off: u64, len: usize) -> Option<&[u8]> {
+        let _ = (off, len);
+        None
+    }
+
+    /// Mutable version of [`Device::slice`].
+    fn slice_mut(&mut self, off: u64, len: usize) -> Option<&mut [u8]> {
+        let _ = (off, len);
+        None
+    }
+
+    /// Put this device back into its power-on state.
+    ///
+    /// Memory keeps its content, so a program that was loaded before is still there after
+    /// a reset. The default implementation does nothing.
+    fn reset(&mut self) {}
+}
+
+/// The access permissions of a memory region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Permissions {
+    /// Whether loads from this region are allowed.
+    pub read: bool,
+    /// Whether stores to this region are allowed.
+    pub write: bool,
+    /// Whether instructions can be fetched from this region.
+    pub execute: bool,
+}
+
+impl Permissions {
+    /// Permissions that allow every kind of access.
+    pub const fn all() -> Self {
+        Self {
+            read: true,
+            write: true,
+            execute: true,
+        }
+    }
+
+    /// Permissions for memory mapped I/O, which can not be executed.
+    pub const fn read_write() -> Self {
+        Self {
+            read: true,
+            write: true,
+            execute: false,
+        }
+    }
+
+    /// Permissions for a ROM, which can not be written to.
+    pub const fn read_execute() -> Self {
+        Self {
+            read: true,
+            write: false,
+            execute: true,
+        }
+    }
+}
+
+/// Information about a single region that is mapped into a [`DeviceBus`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegionInfo {
+    /// The first address that is covered by this region.
+    pub base: Address,
+    /// The number of bytes this region covers.
+    pub size: u64,
+    /// The kind of device that backs this region, as returned by [`Device::kind`].
+    pub kind: &'static str,
+    /// The access permissions of this region.
+    pub permissions: Permissions,
+}
+
+/// Errors that can occur while changing the memory map of a [`DeviceBus`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryMapError {
+    /// The new device would overlap with a device that is already mapped.
+    Overlap {
+        /// The region the new device would cover.
+        new: MemoryRegionInfo,
+        /// The already mapped region that is overlapped.
+        existing: MemoryRegionInfo,
+    },
+    /// The end of the new device would not fit into the address space.
+    OutOfRange {
+        /// The base address of the new device.
+        base: Address,
+        /// The size of the new device.
+        size: u64,
+    },
+    /// There is no device mapped at the given base address.
+    NoDevice(Address),
+    /// The new device has a size of zero, and thus can not be mapped.
+    EmptyDevice(Address),
+}
+
+impl fmt::Display for MemoryMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryMapError::Overlap { new, existing } => write!(
+                f,
+                "{} device at {:#x}..{:#x} overlaps with {} device at {:#x}..{:#x}",
+                new.kind,
+                u64::from(new.base),
+                u64::from(new.base) + new.size,
+                existing.kind,
+                u64::from(existing.base),
+                u64::from(existing.base) + existing.size,
+            ),
+            MemoryMapError::OutOfRange { base, size } => write!(
+                f,
+                "device at {:#x} with size {:#x} exceeds the address space",
+                u64::from(*base),
+                size
+            ),
+            MemoryMapError::NoDevice(base) => {
+                write!(f, "no device is mapped at {:#x}", u64::from(*base))
+            }
+            MemoryMapError::EmptyDevice(base) => {
+                write!(f, "device at {:#x} has a size of zero", u64::from(*base))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MemoryMapError {}
+
+/// A device that is mapped into the bus, together with its attributes.
+struct Region {
+    dev: Box<dyn Device>,
+    permissions: Permissions,
+}
+
+impl Region {
+    fn info(&self, base: Address) -> MemoryRegionInfo {
+        MemoryRegionInfo {
+            base,
+            size: self.dev.size(),
+            kind: self.dev.kind(),
+            permissions: self.permissions,
+        }
+    }
+}
+
+/// What happens if a store modifies a page, that instructions have been fetched from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeWritePolicy {
+    /// Allow the store, without keeping track of it.
+    Allow,
+    /// Allow the store, and remember the modified page, so it can be returned by
+    /// [`DeviceBus::take_modified_code`], e.g. to invalidate decoded instructions.
+    Record,
+    /// Like [`CodeWritePolicy::Record`], but also remember the address of every such store,
+    /// so it can be reported using [`DeviceBus::take_code_write_warnings`].
+    Warn,
+    /// Reject the store with a `StoreAccessFault`.
+    Fault,
 }
 
 /// The emulation of a data bus that contains a bunch of devices at specific addresses.
 ///
 /// Used for reading and writing memory.
 pub struct DeviceBus {
-    devices: HashMap<Address, Box<dyn Device>>,
+    devices: BTreeMap<Address, Region>,
+    /// The numbers of all pages that instructions have been fetched from.
+    executed: RefCell<BTreeSet<u64>>,
+    /// The page of the last fetch, which is already part of `executed`.
+    last_executed: Cell<Option<u64>>,
+    /// The numbers of the executed pages, that were modified afterwards.
+    modified_code: BTreeSet<u64>,
+    /// The addresses of stores into executed pages, if the policy is [`CodeWritePolicy::Warn`].
+    code_write_warnings: Vec<Address>,
+    code_write_policy: CodeWritePolicy,
 }
 
 impl Default for DeviceBus {
@@ -64,43 +320,224 @@ impl Default for DeviceBus {
 impl DeviceBus {
     /// Create a new memory bus with a RAM device with [`DEFAULT_MEMORY_SIZE`] bytes.
     pub fn new() -> Self {
-        let mut bus = DeviceBus {
-            devices: HashMap::new(),
-        };
-        bus.add_device(DRAM_BASE.into(), RamDevice::new(DEFAULT_MEMORY_SIZE));
+        let mut bus = Self::empty();
+        bus.add_device(DRAM_BASE.into(), RamDevice::new(DEFAULT_MEMORY_SIZE))
+            .expect("the empty bus can not contain overlapping devices");
         bus
     }
 
-    /// Load an object file that was previously parsed by the [`object`] crate.
-    pub fn load_object(&mut self, obj: File<'_>) -> object::Result<()> {
-        // FIXME: Check for RISC-V architecture
-        assert!(obj.is_little_endian(), "Big Endian not supported");
+    /// Create a memory bus without any devices.
+    fn empty() -> Self {
+        Self {
+            devices: BTreeMap::new(),
+            executed: RefCell::new(BTreeSet::new()),
+            last_executed: Cell::new(None),
+            modified_code: BTreeSet::new(),
+            code_write_warnings: Vec::new(),
+            code_write_policy: CodeWritePolicy::Record,
+        }
+    }
+
+    /// Add a new device to this memory bus, that starts at the `base` address.
+    ///
+    /// The region will allow every kind of access, use [`DeviceBus::add_device_with`]
+    /// to restrict the permissions of the region.
+    ///
+    /// # Returns
+    ///
+    /// An error if the device would overlap with any device that is already part of this bus.
+    /// In that case, the bus is left unchanged.
+    pub fn add_device(
+        &mut self,
+        base: Address,
+        dev: impl Device + 'static,
+    ) -> core::result::Result<(), MemoryMapError> {
+        self.add_device_with(base, dev, Permissions::all())
+    }
+
+    /// Add a new device to this memory bus, that starts at the `base` address and can only be
+    /// accessed as allowed by `permissions`.
+    ///
+    /// Accesses that violate the permissions will raise the corresponding access fault.
+    pub fn add_device_with(
+        &mut self,
+        base: Address,
+        dev: impl Device + 'static,
+        permissions: Permissions,
+    ) -> core::result::Result<(), MemoryMapError> {
+        let region = Region {
+            dev: Box::new(dev),
+            permissions,
+        };
+        self.check_overlap(&region.info(base), None)?;
+
+        self.devices.insert(base, region);
+        Ok(())
+    }
+
+    /// Replace the device that is mapped at exactly `base` with `dev`, keeping the permissions
+    /// of the region.
+    ///
+    /// # Returns
+    ///
+    /// The device that was replaced, or an error if there is no device at `base` or the new
+    /// device would overlap with any other device.
+    pub fn replace_device(
+        &mut self,
+        base: Address,
+        dev: impl Device + 'static,
+    ) -> core::result::Result<Box<dyn Device>, MemoryMapError> {
+        let permissions = self
+            .devices
+            .get(&base)
+            .ok_or(MemoryMapError::NoDevice(base))?
+            .permissions;
+        let region = Region {
+            dev: Box::new(dev),
+            permissions,
+        };
+        self.check_overlap(&region.info(base), Some(base))?;
+
+        let old = self.devices.insert(base, region).map(|region| region.dev);
+        Ok(old.expect("device was checked to exist"))
+    }
+
+    /// Remove the device that is mapped at exactly `base` from this bus.
+    pub fn remove_device(&mut self, base: Address) -> Option<Box<dyn Device>> {
+        self.devices.remove(&base).map(|region| region.dev)
+    }
+
+    /// Create an independent copy of this bus, by forking every device that is mapped into it.
+    ///
+    /// Forking a bus that only contains RAM devices is cheap, because the memory is only
+    /// copied when either of the buses writes to it.
+    ///
+    /// # Returns
+    ///
+    /// `None` if any of the devices does not support forking.
+    pub fn fork(&self) -> Option<Self> {
+        let devices = self
+            .devices
+            .iter()
+            .map(|(&base, region)| {
+                let region = Region {
+                    dev: region.dev.fork()?,
+                    permissions: region.permissions,
+                };
+                Some((base, region))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            devices,
+            executed: self.executed.clone(),
+            last_executed: self.last_executed.clone(),
+            modified_code: self.modified_code.clone(),
+            code_write_warnings: self.code_write_warnings.clone(),
+            code_write_policy: self.code_write_policy,
+        })
+    }
+
+    /// Set what happens if a store modifies a page that instructions have been fetched from.
+    ///
+    /// The default is [`CodeWritePolicy::Record`].
+    pub fn set_code_write_policy(&mut self, policy: CodeWritePolicy) {
+        self.code_write_policy = policy;
+    }
 
-        // go through each section that is not at address zero and has no zero size
-        for seg in obj.segments() {
-            // first, get the predefined data from the file
-            let mut data = seg.data()?.to_vec();
+    /// Return the addresses of all pages that have been executed and then modified, since the
+    /// last call to this method. The addresses are sorted and aligned to [`PAGE_SIZE`].
+    pub fn take_modified_code(&mut self) -> impl Iterator<Item = Address> {
+        core::mem::take(&mut self.modified_code)
+            .into_iter()
+            .map(|page| Address::from(page * PAGE_SIZE))
+    }
 
-            // then extend the segment to it's real size with zeroes
-            data.resize(seg.size() as usize, 0);
+    /// Return the addresses of all stores into executed pages, that happened while the
+    /// policy was [`CodeWritePolicy::Warn`], since the last call to this method.
+    pub fn take_code_write_warnings(&mut self) -> impl Iterator<Item = Address> {
+        core::mem::take(&mut self.code_write_warnings).into_iter()
+    }
 
-            // write the data into the RAM device
-            let addr = seg.address().into();
-            let (&offset, dev) = self
-                .device_for_mut(addr)
-                .expect("failed to find device to write ELF segment into");
+    /// Check if the `len` bytes at `addr` are inside a single, writable region.
+    fn is_writable(&self, addr: Address, len: usize) -> bool {
+        matches!(self.region_for(addr, len as u64), Some((_, region)) if region.permissions.write)
+    }
 
-            dev.write(u64::from(addr) - u64::from(offset), &data)
-                .expect("failed to write ELF segment to device");
+    /// Apply the [`CodeWritePolicy`] to a store of `len` bytes at `addr`.
+    fn check_code_write(&mut self, addr: Address, len: usize) -> Result<()> {
+        let executed = self.executed.get_mut();
+        if executed.is_empty() || self.code_write_policy == CodeWritePolicy::Allow {
+            return Ok(());
         }
 
+        // this runs before the access is validated, so `addr` may be close to the end
+        let first = u64::from(addr) / PAGE_SIZE;
+        let last = u64::from(addr).saturating_add(len.max(1) as u64 - 1) / PAGE_SIZE;
+        let mut modified = false;
+        for page in executed.range(first..=last) {
+            if self.code_write_policy == CodeWritePolicy::Fault {
+                return Err(Exception::StoreAccessFault(addr));
+            }
+            self.modified_code.insert(*page);
+            modified = true;
+        }
+
+        if modified && self.code_write_policy == CodeWritePolicy::Warn {
+            self.code_write_warnings.push(addr);
+        }
         Ok(())
     }
 
-    /// Add a new device to this memory bus, that starts at the `base` address.
-    pub fn add_device(&mut self, base: Address, dev: impl Device + 'static) {
-        // FIXME: check overlap of addresses here
-        self.devices.insert(base, Box::new(dev));
+    /// Reset every device on this bus, by calling [`Device::reset`].
+    pub fn reset(&mut self) {
+        for region in self.devices.values_mut() {
+            region.dev.reset();
+        }
+    }
+
+    /// Return an iterator over all regions that are mapped into this bus, sorted by their base
+    /// address.
+    pub fn regions(&self) -> impl Iterator<Item = MemoryRegionInfo> + '_ {
+        self.devices.iter().map(|(&base, region)| region.info(base))
+    }
+
+    /// Check if `new` would overlap with any existing region, ignoring the region at `ignore`.
+    fn check_overlap(
+        &self,
+        new: &MemoryRegionInfo,
+        ignore: Option<Address>,
+    ) -> core::result::Result<(), MemoryMapError> {
+        // an empty region would be skipped by the search below, and then silently
+        // replace a region that starts at the same address
+        if new.size == 0 {
+            return Err(MemoryMapError::EmptyDevice(new.base));
+        }
+
+        let start = u64::from(new.base);
+        let end = start
+            .checked_add(new.size)
+            .ok_or(MemoryMapError::OutOfRange {
+                base: new.base,
+                size: new.size,
+            })?;
+
+        // the regions are sorted and do not overlap, so only the last region that starts
+        // below `end` can overlap with the new one
+        let existing = self
+            .devices
+            .range(..Address::from(end))
+            .rfind(|(&base, _)| Some(base) != ignore);
+
+        match existing {
+            Some((&base, region)) if start < u64::from(base) + region.dev.size() => {
+                Err(MemoryMapError::Overlap {
+                    new: new.clone(),
+                    existing: region.info(base),
+                })
+            }
+            _ => Ok(()),
+        }
     }
 
     /// Read a `T` from the given address.
@@ -114,19 +551,58 @@ impl DeviceBus {
             return Err(Exception::LoadAddressMisaligned(addr));
         }
 
-        // find the device that has the smallest, positive distance
-        // from the requested address
-        let (&offset, device) = self.device_for(addr).ok_or(Exception::LoadAccessFault)?;
+        // find the device that contains the whole access, so an access that straddles
+        // the end of a device is rejected before touching any of them
+        let (&offset, region) = self
+            .region_for(addr, size_of::<T>() as u64)
+            .filter(|(_, region)| region.permissions.read)
+            .ok_or(Exception::LoadAccessFault(addr))?;
 
         // create a zeroed `T` to read into
         let mut item = T::zeroed();
-        device.load(
-            u64::from(addr) - u64::from(offset),
-            bytemuck::bytes_of_mut(&mut item),
-        )?;
+        region
+            .dev
+            .load(
+                u64::from(addr) - u64::from(offset),
+                bytemuck::bytes_of_mut(&mut item),
+            )
+            .map_err(|err| err.at(addr))?;
         Ok(item.process_read())
     }
 
+    /// Fetch the raw instruction at the given address.
+    ///
+    /// In contrast to [`DeviceBus::read`], this requires the region to be executable.
+    pub fn fetch(&self, addr: Address) -> Result<u32> {
+        // instructions must be aligned to a 32-bit boundary
+        if u64::from(addr) & 0b11 != 0 {
+            return Err(Exception::InstructionAddressMisaligned(addr));
+        }
+
+        let (&offset, region) = self
+            .region_for(addr, 4)
+            .filter(|(_, region)| region.permissions.execute)
+            .ok_or(Exception::InstructionAccessFault(addr))?;
+
+        let mut inst = 0u32;
+        region
+            .dev
+            .load(
+                u64::from(addr) - u64::from(offset),
+                bytemuck::bytes_of_mut(&mut inst),
+            )
+            .map_err(|_| Exception::InstructionAccessFault(addr))?;
+
+        // only pages that instructions were actually fetched from count as code. most fetches
+        // hit the same page as the previous one, which is already known
+        let page = u64::from(addr) / PAGE_SIZE;
+        if self.last_executed.get() != Some(page) {
+            self.executed.borrow_mut().insert(page);
+            self.last_executed.set(Some(page));
+        }
+        Ok(inst.process_read())
+    }
+
     /// Write a `T` to the given address.
     ///
     /// # Returns
@@ -139,39 +615,91 @@ impl DeviceBus {
             return Err(Exception::StoreAddressMisaligned(addr));
         }
 
-        // find the first device that contains the given address
-        let (&offset, device) = self
-            .device_for_mut(addr)
-            .ok_or(Exception::StoreAccessFault)?;
+        // find the device that contains the whole access, so a faulting store
+        // never leaves memory partially written
+        if !self.is_writable(addr, size_of::<T>()) {
+            return Err(Exception::StoreAccessFault(addr));
+        }
+        self.check_code_write(addr, size_of::<T>())?;
+
+        let (&offset, region) = self
+            .region_for_mut(addr, size_of::<T>() as u64)
+            .expect("region was checked above");
 
         // write the item into the device
         let item = item.process_write();
-        device.write(
-            u64::from(addr) - u64::from(offset),
-            bytemuck::bytes_of(&item),
-        )?;
-        Ok(())
+        region
+            .dev
+            .write(
+                u64::from(addr) - u64::from(offset),
+                bytemuck::bytes_of(&item),
+            )
+            .map_err(|err| err.at(addr))
     }
 
-    #[allow(clippy::borrowed_box)]
-    fn device_for(&self, addr: Address) -> Option<(&Address, &Box<dyn Device>)> {
-        self.devices.iter().find(|(&k, v)| {
-            let base = u64::from(k);
-            let end = base + v.size();
-            let addr = u64::from(addr);
+    /// Return the `len` bytes at `addr` as a slice directly into the memory of a device,
+    /// avoiding a copy through [`Device::load`].
+    ///
+    /// RAM devices are split into pages, so a slice can not cross a page boundary.
+    ///
+    /// # Returns
+    ///
+    /// A `LoadAccessFault` if the range is not readable, or is not backed by contiguous
+    /// memory, in which case [`DeviceBus::read`] has to be used.
+    pub fn slice(&self, addr: Address, len: usize) -> Result<&[u8]> {
+        let (&base, region) = self
+            .region_for(addr, len as u64)
+            .filter(|(_, region)| region.permissions.read)
+            .ok_or(Exception::LoadAccessFault(addr))?;
 
-            base <= addr && addr < end
-        })
+        region
+            .dev
+            .slice(u64::from(addr) - u64::from(base), len)
+            .ok_or(Exception::LoadAccessFault(addr))
     }
 
-    fn device_for_mut(&mut self, addr: Address) -> Option<(&Address, &mut Box<dyn Device>)> {
-        self.devices.iter_mut().find(|(&k, v)| {
-            let base = u64::from(k);
-            let end = base + v.size();
-            let addr = u64::from(addr);
+    /// Mutable version of [`DeviceBus::slice`], which requires the range to be writable.
+    pub fn slice_mut(&mut self, addr: Address, len: usize) -> Result<&mut [u8]> {
+        if !self.is_writable(addr, len) {
+            return Err(Exception::StoreAccessFault(addr));
+        }
+        self.check_code_write(addr, len)?;
 
-            base <= addr && addr < end
-        })
+        let (&base, region) = self
+            .region_for_mut(addr, len as u64)
+            .expect("region was checked above");
+
+        region
+            .dev
+            .slice_mut(u64::from(addr) - u64::from(base), len)
+            .ok_or(Exception::StoreAccessFault(addr))
+    }
+
+    /// Find the region that contains the whole access of `len` bytes at `addr`.
+    fn region_for(&self, addr: Address, len: u64) -> Option<(&Address, &Region)> {
+        // the only candidate is the region with the largest base that is not above `addr`
+        let (base, region) = self.devices.range(..=addr).next_back()?;
+
+        if Self::contains(*base, region, addr, len) {
+            Some((base, region))
+        } else {
+            None
+        }
+    }
+
+    fn region_for_mut(&mut self, addr: Address, len: u64) -> Option<(&Address, &mut Region)> {
+        let (base, region) = self.devices.range_mut(..=addr).next_back()?;
+
+        if Self::contains(*base, region, addr, len) {
+            Some((base, region))
+        } else {
+            None
+        }
+    }
+
+    fn contains(base: Address, region: &Region, addr: Address, len: u64) -> bool {
+        let off = u64::from(addr) - u64::from(base);
+        matches!(off.checked_add(len), Some(end) if end <= region.dev.size())
     }
 }
 
@@ -206,6 +734,7 @@ impl_int!(usize, u8, u16, u32, u64, isize, i8, i16, i32, i64);
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::{vec, vec::Vec};
 
     #[test]
     fn read_write_ram() {
@@ -213,11 +742,820 @@ mod tests {
 
         assert_eq!(
             mem.read::<u64>(0x6000_0000u32.into()),
-            Err(Exception::LoadAccessFault)
+            Err(Exception::LoadAccessFault(Address::from(0x6000_0000u32)))
         );
         assert_eq!(mem.read::<u64>(0x8000_0000u32.into()), Ok(0u64));
 
         assert_eq!(mem.write::<u64>(0x8000_0000u32.into(), 0x1234), Ok(()));
         assert_eq!(mem.read::<u64>(0x8000_0000u32.into()), Ok(0x1234));
     }
+
+    #[test]
+    fn read_write_sparse_ram() {
+        let mut mem = DeviceBus::new();
+        mem.add_device(0x1_0000_0000u64.into(), SparseRamDevice::new(4 << 30))
+            .unwrap();
+
+        // accesses near the end of the 4GiB region
+        let addr = 0x1_0000_0000u64 + (4 << 30) - 0x1008;
+        assert_eq!(mem.read::<u64>(addr.into()), Ok(0));
+        assert_eq!(mem.write::<u32>(addr.into(), 0xDEAD_BEEF), Ok(()));
+        assert_eq!(mem.write::<u32>((addr + 4).into(), 0x1234_5678), Ok(()));
+        assert_eq!(mem.read::<u64>(addr.into()), Ok(0x1234_5678_DEAD_BEEF));
+        assert_eq!(mem.read::<u32>((addr + 0x1004).into()), Ok(0));
+        assert_eq!(
+            mem.read::<u32>((addr + 0x1008).into()),
+            Err(Exception::LoadAccessFault(Address::from(addr + 0x1008)))
+        );
+    }
+
+    #[test]
+    fn fork_bus() {
+        let mut mem = DeviceBus::new();
+        mem.add_device(0x1000_0000u32.into(), SparseRamDevice::new(0x10_0000))
+            .unwrap();
+        mem.write::<u32>(DRAM_BASE.into(), 0x1234).unwrap();
+        mem.write::<u32>(0x1000_0000u32.into(), 0x5678).unwrap();
+
+        let mut fork = mem.fork().unwrap();
+        fork.write::<u32>(DRAM_BASE.into(), 0xAAAA).unwrap();
+        fork.write::<u32>(0x1000_0000u32.into(), 0xBBBB).unwrap();
+        mem.write::<u32>((DRAM_BASE + 4).into(), 0xCCCC).unwrap();
+
+        assert_eq!(mem.read::<u32>(DRAM_BASE.into()), Ok(0x1234));
+        assert_eq!(mem.read::<u32>(0x1000_0000u32.into()), Ok(0x5678));
+        assert_eq!(fork.read::<u32>(DRAM_BASE.into()), Ok(0xAAAA));
+        assert_eq!(fork.read::<u32>(0x1000_0000u32.into()), Ok(0xBBBB));
+        assert_eq!(fork.read::<u32>((DRAM_BASE + 4).into()), Ok(0));
+    }
+
+    #[test]
+    fn scripted_mock_device() {
+        let mut mem = DeviceBus::new();
+        let mock = MockDevice::new(0x100)
+            .with_read(0x0, 0x41u32)
+            .with_read_sequence(0x4, [0u32, 0, 1])
+            .with_read(0x4, 2u32)
+            .expect_write(0x8, 0xFFu8)
+            .expect_write(0x0, 0x1234u32);
+        let handle = mock.handle();
+        mem.add_device(0x1000_0000u32.into(), mock).unwrap();
+
+        assert_eq!(mem.read::<u32>(0x1000_0000u32.into()), Ok(0x41));
+        assert_eq!(mem.read::<u32>(0x1000_0000u32.into()), Ok(0x41));
+        let status = (0..4)
+            .map(|_| mem.read::<u32>(0x1000_0004u32.into()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(status, [0, 0, 1, 2]);
+        assert_eq!(
+            mem.read::<u16>(0x1000_0000u32.into()),
+            Err(Exception::LoadAccessFault(Address::from(0x1000_0000u32)))
+        );
+        assert_eq!(
+            mem.read::<u32>(0x1000_000Cu32.into()),
+            Err(Exception::LoadAccessFault(Address::from(0x1000_000Cu32)))
+        );
+
+        mem.write::<u8>(0x1000_0008u32.into(), 0xFF).unwrap();
+        assert_eq!(handle.pending_writes(), 1);
+        mem.write::<u32>(0x1000_0000u32.into(), 0x1234).unwrap();
+        handle.assert_done();
+        assert_eq!(
+            handle.writes(),
+            [(0x8, vec![0xFF]), (0x0, vec![0x34, 0x12, 0, 0])]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected write")]
+    fn mock_device_rejects_extra_writes() {
+        let mut mock = MockDevice::new(0x10).expect_write(0x0, 1u8);
+        mock.write(0x0, &[1]).unwrap();
+        let _ = mock.write(0x4, &[2]);
+    }
+
+    #[test]
+    fn recording_mock_device() {
+        let mut mock = MockDevice::new(0x10).record_writes();
+        let handle = mock.handle();
+        mock.write(0x4, &[2]).unwrap();
+        assert_eq!(handle.writes(), [(0x4, vec![2])]);
+    }
+
+    #[test]
+    fn sd_card_over_spi() {
+        const SPI: u64 = 0x1001_0000;
+
+        fn xfer(mem: &mut DeviceBus, byte: u8) -> u8 {
+            mem.write::<u32>((SPI + 0x48).into(), byte as u32).unwrap();
+            mem.read::<u32>((SPI + 0x4C).into()).unwrap() as u8
+        }
+
+        fn command(mem: &mut DeviceBus, cmd: u8, arg: u32) -> u8 {
+            let [a, b, c, d] = arg.to_be_bytes();
+            for byte in [0x40 | cmd, a, b, c, d, 0x95] {
+                xfer(mem, byte);
+            }
+            (0..8)
+                .map(|_| xfer(mem, 0xFF))
+                .find(|&r1| r1 != 0xFF)
+                .unwrap()
+        }
+
+        let mut mem = DeviceBus::new();
+        let card = SdCard::new(vec![0; 4 * 512]);
+        mem.add_device(SPI.into(), SpiController::new(card))
+            .unwrap();
+
+        // send clocks with the card deselected, then select it
+        mem.write::<u32>((SPI + 0x18).into(), 3).unwrap();
+        for _ in 0..10 {
+            assert_eq!(xfer(&mut mem, 0xFF), 0xFF);
+        }
+        mem.write::<u32>((SPI + 0x18).into(), 2).unwrap();
+
+        assert_eq!(command(&mut mem, 17, 0), 0x05);
+        assert_eq!(command(&mut mem, 0, 0), 0x01);
+        assert_eq!(command(&mut mem, 8, 0x1AA), 0x01);
+        let echo = (0..4).map(|_| xfer(&mut mem, 0xFF)).collect::<Vec<_>>();
+        assert_eq!(echo, [0x00, 0x00, 0x01, 0xAA]);
+        assert_eq!(command(&mut mem, 55, 0), 0x01);
+        assert_eq!(command(&mut mem, 41, 1 << 30), 0x00);
+
+        // write a block
+        assert_eq!(command(&mut mem, 24, 1), 0x00);
+        xfer(&mut mem, 0xFF);
+        xfer(&mut mem, 0xFE);
+        for i in 0..512 {
+            xfer(&mut mem, i as u8);
+        }
+        xfer(&mut mem, 0);
+        xfer(&mut mem, 0);
+        assert_eq!(xfer(&mut mem, 0xFF) & 0x1F, 0x05);
+
+        // and read it back
+        assert_eq!(command(&mut mem, 17, 1), 0x00);
+        while xfer(&mut mem, 0xFF) != 0xFE {}
+        let block = (0..512).map(|_| xfer(&mut mem, 0xFF)).collect::<Vec<_>>();
+        assert!(block.iter().enumerate().all(|(i, &x)| x == i as u8));
+
+        assert_eq!(command(&mut mem, 17, 4), 0x20);
+
+        // after a reset the card has to be initialized again, but keeps its content
+        mem.write::<u32>((SPI + 0x18).into(), 2).unwrap();
+        mem.reset();
+        assert_eq!(mem.read::<u32>((SPI + 0x18).into()), Ok(0));
+        assert_eq!(command(&mut mem, 17, 1), 0x05);
+        assert_eq!(command(&mut mem, 0, 0), 0x01);
+        assert_eq!(command(&mut mem, 55, 0), 0x01);
+        assert_eq!(command(&mut mem, 41, 1 << 30), 0x00);
+        assert_eq!(command(&mut mem, 17, 1), 0x00);
+        while xfer(&mut mem, 0xFF) != 0xFE {}
+        assert_eq!(xfer(&mut mem, 0xFF), 0);
+        assert_eq!(xfer(&mut mem, 0xFF), 1);
+    }
+
+    #[test]
+    fn gpio_outputs() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let gpio = GpioController::new().on_change({
+            let changes = Rc::clone(&changes);
+            move |old, new| changes.borrow_mut().push((old, new))
+        });
+        let handle = gpio.handle();
+
+        let mut mem = DeviceBus::new();
+        mem.add_device(0x1001_2000u32.into(), gpio).unwrap();
+
+        // the LED is only driven once the pin is enabled as an output
+        mem.write::<u32>(0x1001_200Cu32.into(), 0b101).unwrap();
+        assert_eq!(handle.outputs(), 0);
+        mem.write::<u32>(0x1001_2008u32.into(), 0b001).unwrap();
+        assert!(handle.output(0));
+        assert!(!handle.output(2));
+        mem.write::<u32>(0x1001_200Cu32.into(), 0b100).unwrap();
+        assert_eq!(*changes.borrow(), [(0b000, 0b001), (0b001, 0b000)]);
+
+        // inputs are only visible if enabled
+        handle.set_input(3, true);
+        assert_eq!(mem.read::<u32>(0x1001_2000u32.into()), Ok(0));
+        mem.write::<u32>(0x1001_2004u32.into(), 1 << 3).unwrap();
+        assert_eq!(mem.read::<u32>(0x1001_2000u32.into()), Ok(1 << 3));
+
+        // a reset disables all pins, but keeps the level driven by the host
+        mem.write::<u32>(0x1001_200Cu32.into(), 0b101).unwrap();
+        mem.write::<u32>((DRAM_BASE + 0x10).into(), 0xDEAD_BEEF)
+            .unwrap();
+        mem.reset();
+        assert_eq!(handle.outputs(), 0);
+        assert_eq!(changes.borrow().last(), Some(&(0b001, 0b000)));
+        assert_eq!(mem.read::<u32>(0x1001_2000u32.into()), Ok(0));
+        mem.write::<u32>(0x1001_2004u32.into(), 1 << 3).unwrap();
+        assert_eq!(mem.read::<u32>(0x1001_2000u32.into()), Ok(1 << 3));
+        assert_eq!(mem.read::<u32>((DRAM_BASE + 0x10).into()), Ok(0xDEAD_BEEF));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid pin: 32")]
+    fn gpio_invalid_pin() {
+        GpioController::new().handle().set_input(32, true);
+    }
+
+    #[test]
+    fn shared_memory() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        const BASE: u64 = 0x2000_0000;
+        const BUF: u64 = BASE + SharedMemory::BUFFER_OFFSET;
+
+        let rings = Rc::new(RefCell::new(Vec::new()));
+        let shmem = SharedMemory::new(0x100).on_doorbell({
+            let rings = Rc::clone(&rings);
+            move |val| rings.borrow_mut().push(val)
+        });
+        let handle = shmem.handle();
+
+        let mut mem = DeviceBus::new();
+        mem.add_device(BASE.into(), shmem).unwrap();
+        assert_eq!(mem.read::<u32>((BASE + 0x08).into()), Ok(0x100));
+
+        // host to guest
+        assert!(handle.write(0x10, &[1, 2, 3, 4]));
+        handle.notify(0b10);
+        assert_eq!(mem.read::<u32>((BASE + 0x04).into()), Ok(0b10));
+        assert_eq!(mem.read::<u32>((BUF + 0x10).into()), Ok(0x0403_0201));
+        assert!(handle.interrupt_pending());
+        mem.write::<u32>((BASE + 0x04).into(), 0b10).unwrap();
+        assert_eq!(handle.pending(), 0);
+        assert!(!handle.interrupt_pending());
+
+        // guest to host
+        mem.write::<u64>((BUF + 0xF8).into(), u64::MAX).unwrap();
+        mem.write::<u32>(BASE.into(), 7).unwrap();
+        let mut buf = [0; 8];
+        assert!(handle.read(0xF8, &mut buf));
+        assert_eq!(buf, [0xFF; 8]);
+        assert_eq!(*rings.borrow(), [7]);
+        assert_eq!(
+            mem.read::<u8>((BUF + 0x100).into()),
+            Err(Exception::LoadAccessFault(Address::from(BUF + 0x100)))
+        );
+
+        // out of bounds accesses are rejected instead of panicking
+        assert!(!handle.read(0xFC, &mut buf));
+        assert!(!handle.write(usize::MAX, &[0]));
+        let mut shmem = SharedMemory::new(0x10);
+        let end = SharedMemory::BUFFER_OFFSET + 0x0C;
+        assert_eq!(
+            shmem.load(end, &mut buf),
+            Err(Exception::LoadAccessFault(Address::from(end)))
+        );
+        assert_eq!(
+            shmem.write(u64::MAX, &buf),
+            Err(Exception::StoreAccessFault(Address::from(u64::MAX)))
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", unix))]
+    fn remote_device() {
+        use std::os::unix::net::UnixStream;
+
+        let (bus_side, dev_side) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut ram = RamDevice::new(0x100);
+            remote::serve(dev_side, &mut ram).map(|()| ram)
+        });
+
+        let mut mem = DeviceBus::new();
+        mem.add_device(0x1000u32.into(), RemoteDevice::new(0x100, bus_side))
+            .unwrap();
+        mem.write::<u32>(0x1010u32.into(), 0xDEAD_BEEF).unwrap();
+        assert_eq!(mem.read::<u32>(0x1010u32.into()), Ok(0xDEAD_BEEF));
+        assert_eq!(mem.read::<u8>(0x1013u32.into()), Ok(0xDE));
+
+        // faults of the remote device are forwarded
+        let mut remote = mem.remove_device(0x1000u32.into()).unwrap();
+        assert_eq!(
+            remote.load(0x100, &mut [0]),
+            Err(Exception::LoadAccessFault(Address::from(0x100u32)))
+        );
+
+        // invalid requests are rejected without losing track of the stream
+        assert_eq!(
+            remote.write(0xFE, &[0; 4]),
+            Err(Exception::StoreAccessFault(Address::from(0xFEu32)))
+        );
+        assert_eq!(
+            remote.write(0, &vec![0; 0x2000]),
+            Err(Exception::StoreAccessFault(Address::zero()))
+        );
+        let mut buf = [0; 4];
+        remote.load(0x10, &mut buf).unwrap();
+        assert_eq!(u32::from_le_bytes(buf), 0xDEAD_BEEF);
+        drop(remote);
+
+        let ram = server.join().unwrap().unwrap();
+        let mut buf = [0; 4];
+        ram.load(0x10, &mut buf).unwrap();
+        assert_eq!(u32::from_le_bytes(buf), 0xDEAD_BEEF);
+
+        // interrupt lines are polled from the device side
+        let (bus_side, dev_side) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut ram = RamDevice::new(0x100);
+            remote::serve_with_interrupts(dev_side, &mut ram, || 0b101)
+        });
+        let remote = RemoteDevice::new(0x100, bus_side);
+        assert_eq!(remote.interrupts().unwrap(), 0b101);
+        drop(remote);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn load_elf_into_dram() {
+        let elf = include_bytes!("../tests/binaries/rv32ui-p/rv32ui-p-add");
+        let mut mem = DeviceBus::new();
+
+        mem.write::<u32>((DRAM_BASE + 0x1044).into(), 0xFFFF_FFFF)
+            .unwrap();
+        assert_eq!(mem.load_object(elf).unwrap(), DRAM_BASE.into());
+
+        // the first instruction is `j reset_vector`
+        assert_eq!(mem.fetch(DRAM_BASE.into()), Ok(0x04C0_006F));
+        assert_eq!(mem.read::<u32>((DRAM_BASE + 0x1044).into()), Ok(0));
+        assert_eq!(mem.regions().count(), 1);
+    }
+
+    #[test]
+    fn load_elf_into_empty_bus() {
+        let elf = include_bytes!("../tests/binaries/rv32ui-p/rv32ui-p-add");
+        let mut mem = DeviceBus::empty();
+
+        assert_eq!(mem.load_object(elf).unwrap(), DRAM_BASE.into());
+        let regions = mem.regions().collect::<Vec<_>>();
+        assert_eq!(
+            regions,
+            [
+                MemoryRegionInfo {
+                    base: DRAM_BASE.into(),
+                    size: 0x1000,
+                    kind: "ram",
+                    permissions: Permissions::read_execute(),
+                },
+                MemoryRegionInfo {
+                    base: (DRAM_BASE + 0x1000).into(),
+                    size: 0x1000,
+                    kind: "ram",
+                    permissions: Permissions::read_write(),
+                },
+            ]
+        );
+
+        assert_eq!(mem.fetch(DRAM_BASE.into()), Ok(0x04C0_006F));
+        assert_eq!(
+            mem.write::<u32>(DRAM_BASE.into(), 0),
+            Err(Exception::StoreAccessFault(Address::from(DRAM_BASE)))
+        );
+        assert!(matches!(
+            mem.load_object(b"not an elf"),
+            Err(LoadError::Object(_))
+        ));
+    }
+
+    /// Build an ELF32 executable with a `PT_LOAD` segment for every `(offset, vaddr, filesz,
+    /// memsz)` tuple, followed by 0x40 bytes of `0xAA`.
+    fn segments_elf(segments: &[(u32, u32, u32, u32)]) -> Vec<u8> {
+        let mut elf = vec![0x7F, b'E', b'L', b'F', 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        elf.extend_from_slice(&[2, 0, 243, 0]);
+        [1, 0, 52, 0, 0]
+            .iter()
+            .for_each(|w: &u32| elf.extend_from_slice(&w.to_le_bytes()));
+        elf.extend_from_slice(&[52, 0, 32, 0, segments.len() as u8, 0, 0, 0, 0, 0, 0, 0]);
+
+        for &(offset, vaddr, filesz, memsz) in segments {
+            [1, offset, vaddr, vaddr, filesz, memsz, 0b110, 0x1000]
+                .iter()
+                .for_each(|w| elf.extend_from_slice(&w.to_le_bytes()));
+        }
+        elf.extend_from_slice(&[0xAA; 0x40]);
+        elf
+    }
+
+    #[test]
+    fn load_overlapping_segments() {
+        const BASE: u32 = 0x9000_0000;
+        let data = 52 + 2 * 32;
+        let elf = segments_elf(&[(data, BASE, 0x10, 0x10), (data, BASE + 0x800, 0x10, 0x1000)]);
+
+        // the second segment starts in the page of the first one, but extends past it
+        let mut mem = DeviceBus::empty();
+        mem.load_object(&elf).unwrap();
+        let regions = mem.regions().collect::<Vec<_>>();
+        assert_eq!(regions.len(), 1);
+        assert_eq!((regions[0].base, regions[0].size), (BASE.into(), 0x2000));
+        assert_eq!(mem.read::<u32>(BASE.into()), Ok(0xAAAA_AAAA));
+        assert_eq!(mem.read::<u32>((BASE + 0x80C).into()), Ok(0xAAAA_AAAA));
+        assert_eq!(mem.read::<u32>((BASE + 0x810).into()), Ok(0));
+
+        // nothing stays mapped if a later segment fails
+        let data = 52 + 3 * 32;
+        let elf = segments_elf(&[
+            (data, BASE, 0x10, 0x10),
+            (data, BASE + 0x4000, 0x10, 0x10),
+            (0x1000, BASE + 0x8000, 0x10, 0x10),
+        ]);
+        let mut mem = DeviceBus::empty();
+        assert!(matches!(
+            mem.load_object(&elf),
+            Err(LoadError::InvalidSegment(_))
+        ));
+        assert_eq!(mem.regions().count(), 0);
+
+        // huge segments are rejected instead of allocating memory for them
+        let elf = segments_elf(&[
+            (52 + 2 * 32, BASE, 0x10, 0x10),
+            (52 + 2 * 32, BASE + 0x800, 0x10, 0x4000_0000),
+        ]);
+        assert!(matches!(
+            mem.load_object(&elf),
+            Err(LoadError::SegmentOutOfBounds { .. })
+        ));
+        let elf = segments_elf(&[(52 + 32, 0x1000, 0x10, 0xF000_0000)]);
+        assert!(matches!(
+            mem.load_object(&elf),
+            Err(LoadError::SegmentOutOfBounds { .. })
+        ));
+        assert_eq!(mem.regions().count(), 0);
+    }
+
+    /// Build a tiny position independent ELF32 file, containing a single relative
+    /// relocation for the word at offset 160.
+    fn pie_elf() -> Vec<u8> {
+        let words = |elf: &mut Vec<u8>, words: &[u32]| {
+            words
+                .iter()
+                .for_each(|w| elf.extend_from_slice(&w.to_le_bytes()))
+        };
+
+        let mut elf = vec![0x7F, b'E', b'L', b'F', 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        // e_type, e_machine
+        elf.extend_from_slice(&[3, 0, 243, 0]);
+        // e_version, e_entry, e_phoff, e_shoff, e_flags
+        words(&mut elf, &[1, 0x40, 52, 0, 0]);
+        // e_ehsize, e_phentsize, e_phnum, e_shentsize, e_shnum, e_shstrndx
+        elf.extend_from_slice(&[52, 0, 32, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+
+        // PT_LOAD covering the whole file, and PT_DYNAMIC
+        words(&mut elf, &[1, 0, 0, 0, 164, 164, 0b111, 0x1000]);
+        words(&mut elf, &[2, 116, 116, 116, 32, 32, 0b110, 4]);
+        // DT_RELA, DT_RELASZ, DT_RELAENT, DT_NULL
+        words(&mut elf, &[7, 148, 8, 12, 9, 12, 0, 0]);
+        // R_RISCV_RELATIVE
+        words(&mut elf, &[160, 3, 0x80]);
+        // the word that is relocated
+        words(&mut elf, &[0]);
+
+        elf
+    }
+
+    #[test]
+    fn load_pie() {
+        let elf = pie_elf();
+        let mut mem = DeviceBus::new();
+
+        assert_eq!(mem.load_object(&elf).unwrap(), (DRAM_BASE + 0x40).into());
+        assert_eq!(
+            mem.read::<u32>((DRAM_BASE + 160).into()),
+            Ok(DRAM_BASE as u32 + 0x80)
+        );
+
+        let base = DRAM_BASE + 0x10_0000;
+        assert_eq!(
+            mem.load_object_at(&elf, base.into()).unwrap(),
+            (base + 0x40).into()
+        );
+        assert_eq!(mem.read::<u32>((base + 160).into()), Ok(base as u32 + 0x80));
+
+        let exec = include_bytes!("../tests/binaries/rv32ui-p/rv32ui-p-add");
+        assert!(matches!(
+            mem.load_object_at(exec, base.into()),
+            Err(LoadError::NotRelocatable)
+        ));
+
+        // a failed relocation removes the memory that was mapped for the file
+        let mut elf = elf;
+        elf[152] = 2;
+        let regions = mem.regions().count();
+        assert!(matches!(
+            mem.load_object_at(&elf, 0x4000_0000u32.into()),
+            Err(LoadError::UnsupportedRelocation(2))
+        ));
+        assert_eq!(mem.regions().count(), regions);
+
+        // a relocation table with a different entry size is not parsed at all
+        elf[152] = 3;
+        elf[136] = 8;
+        assert!(matches!(
+            mem.load_object_at(&elf, 0x4000_0000u32.into()),
+            Err(LoadError::InvalidRelocations)
+        ));
+    }
+
+    #[test]
+    fn straddling_accesses() {
+        let mut mem = DeviceBus::new();
+        mem.add_device(0x1000u32.into(), RamDevice::new(4)).unwrap();
+        mem.add_device(0x1004u32.into(), RamDevice::new(6)).unwrap();
+        mem.write::<u32>(0x1000u32.into(), 0x1111_1111).unwrap();
+        mem.write::<u32>(0x1004u32.into(), 0x2222_2222).unwrap();
+        mem.write::<u16>(0x1008u32.into(), 0x3333).unwrap();
+
+        // crosses from one device into the next one
+        assert_eq!(
+            mem.write::<u64>(0x1000u32.into(), 0),
+            Err(Exception::StoreAccessFault(Address::from(0x1000u32)))
+        );
+        assert_eq!(
+            mem.read::<u64>(0x1000u32.into()),
+            Err(Exception::LoadAccessFault(Address::from(0x1000u32)))
+        );
+        // crosses the end of the last device
+        assert_eq!(
+            mem.write::<u32>(0x1008u32.into(), 0),
+            Err(Exception::StoreAccessFault(Address::from(0x1008u32)))
+        );
+
+        assert_eq!(mem.read::<u32>(0x1000u32.into()), Ok(0x1111_1111));
+        assert_eq!(mem.read::<u32>(0x1004u32.into()), Ok(0x2222_2222));
+        assert_eq!(mem.read::<u16>(0x1008u32.into()), Ok(0x3333));
+    }
+
+    #[test]
+    fn direct_slices() {
+        let mut mem = DeviceBus::new();
+        mem.add_device(0x1000u32.into(), SparseRamDevice::new(0x2000))
+            .unwrap();
+        mem.add_device(0x4000u32.into(), MockDevice::new(0x10))
+            .unwrap();
+
+        let addr = Address::from(DRAM_BASE + 0x10);
+        mem.slice_mut(addr, 4)
+            .unwrap()
+            .copy_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(mem.read::<u32>(addr), Ok(0x0403_0201));
+        assert_eq!(mem.slice(addr, 4), Ok(&[1, 2, 3, 4][..]));
+
+        // sparse pages read as zero without being allocated
+        assert_eq!(mem.slice(0x1000u32.into(), 8), Ok(&[0; 8][..]));
+        mem.slice_mut(0x2000u32.into(), 1).unwrap()[0] = 0xAB;
+        assert_eq!(mem.read::<u8>(0x2000u32.into()), Ok(0xAB));
+
+        // crossing a page, the end of a device, or a device without memory
+        let page_end = Address::from(DRAM_BASE + PAGE_SIZE - 2);
+        assert_eq!(
+            mem.slice(page_end, 4),
+            Err(Exception::LoadAccessFault(page_end))
+        );
+        assert_eq!(
+            mem.slice(0x2FFCu32.into(), 8),
+            Err(Exception::LoadAccessFault(Address::from(0x2FFCu32)))
+        );
+        assert_eq!(
+            mem.slice(0x4000u32.into(), 4),
+            Err(Exception::LoadAccessFault(Address::from(0x4000u32)))
+        );
+        assert_eq!(
+            mem.slice_mut(0x4000u32.into(), 4),
+            Err(Exception::StoreAccessFault(Address::from(0x4000u32)))
+        );
+    }
+
+    #[test]
+    fn dirty_ram_pages() {
+        let mut ram = RamDevice::new(0x10000);
+        assert_eq!(ram.dirty_pages().count(), 0);
+
+        ram.write(0x10, &[1]).unwrap();
+        ram.write(PAGE_SIZE * 3 - 2, &[1, 2, 3, 4]).unwrap();
+        ram.slice_mut(PAGE_SIZE * 15, 1).unwrap()[0] = 1;
+        ram.load(PAGE_SIZE * 5, &mut [0; 4]).unwrap();
+        assert_eq!(ram.dirty_pages().collect::<Vec<_>>(), [0, 2, 3, 15]);
+
+        ram.clear_dirty();
+        assert!(!ram.is_dirty(0));
+        ram.write(PAGE_SIZE * 7, &[1]).unwrap();
+        assert_eq!(ram.dirty_pages().collect::<Vec<_>>(), [7]);
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        let mut mem = DeviceBus::new();
+        let code = Address::from(DRAM_BASE + 0x2000);
+        let data = Address::from(DRAM_BASE + 0x3000);
+
+        // stores before the page was executed are not tracked
+        mem.write::<u32>(code, 0x13).unwrap();
+        assert_eq!(mem.fetch(code), Ok(0x13));
+        mem.write::<u32>(data, 1).unwrap();
+        assert_eq!(mem.take_modified_code().count(), 0);
+
+        mem.write::<u32>((DRAM_BASE + 0x2010).into(), 0x13).unwrap();
+        mem.slice_mut((DRAM_BASE + 0x2020).into(), 4).unwrap();
+        assert_eq!(mem.take_modified_code().collect::<Vec<_>>(), [code]);
+        assert_eq!(mem.take_modified_code().count(), 0);
+
+        mem.set_code_write_policy(CodeWritePolicy::Fault);
+        assert_eq!(
+            mem.write::<u32>(code, 0),
+            Err(Exception::StoreAccessFault(code))
+        );
+        assert_eq!(mem.fetch(code), Ok(0x13));
+        mem.write::<u32>(data, 2).unwrap();
+
+        mem.set_code_write_policy(CodeWritePolicy::Warn);
+        mem.write::<u16>((DRAM_BASE + 0x2FFE).into(), 0).unwrap();
+        mem.write::<u32>(data, 3).unwrap();
+        assert_eq!(mem.take_modified_code().collect::<Vec<_>>(), [code]);
+        assert_eq!(
+            mem.take_code_write_warnings().collect::<Vec<_>>(),
+            [Address::from(DRAM_BASE + 0x2FFE)]
+        );
+
+        mem.set_code_write_policy(CodeWritePolicy::Allow);
+        mem.write::<u32>(code, 0).unwrap();
+        assert_eq!(mem.take_modified_code().count(), 0);
+        assert_eq!(mem.take_code_write_warnings().count(), 0);
+
+        // stores close to the end of the address space don't overflow
+        mem.set_code_write_policy(CodeWritePolicy::Record);
+        assert_eq!(
+            mem.write::<u8>(Address::from(u64::MAX), 0),
+            Err(Exception::StoreAccessFault(Address::from(u64::MAX)))
+        );
+
+        // failed fetches don't mark the page as code
+        let mock = Address::from(0x1000_0000u32);
+        mem.add_device(mock, MockDevice::new(0x1000).record_writes())
+            .unwrap();
+        mem.set_code_write_policy(CodeWritePolicy::Fault);
+        assert_eq!(
+            mem.fetch(mock),
+            Err(Exception::InstructionAccessFault(mock))
+        );
+        mem.write::<u32>(mock, 0).unwrap();
+    }
+
+    #[test]
+    fn list_regions() {
+        let mut mem = DeviceBus::new();
+        mem.add_device(0x1000u32.into(), RamDevice::new(0x100))
+            .unwrap();
+
+        let regions = mem.regions().collect::<Vec<_>>();
+        assert_eq!(
+            regions,
+            vec![
+                MemoryRegionInfo {
+                    base: 0x1000u32.into(),
+                    size: 0x100,
+                    kind: "ram",
+                    permissions: Permissions::all(),
+                },
+                MemoryRegionInfo {
+                    base: DRAM_BASE.into(),
+                    size: DEFAULT_MEMORY_SIZE as u64,
+                    kind: "ram",
+                    permissions: Permissions::all(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_devices() {
+        let mut mem = DeviceBus::new();
+        let dram = MemoryRegionInfo {
+            base: DRAM_BASE.into(),
+            size: DEFAULT_MEMORY_SIZE as u64,
+            kind: "ram",
+            permissions: Permissions::all(),
+        };
+
+        // overlaps with the start of the DRAM
+        assert_eq!(
+            mem.add_device(0x7FFF_FFF0u32.into(), RamDevice::new(0x20)),
+            Err(MemoryMapError::Overlap {
+                new: MemoryRegionInfo {
+                    base: 0x7FFF_FFF0u32.into(),
+                    size: 0x20,
+                    kind: "ram",
+                    permissions: Permissions::all(),
+                },
+                existing: dram.clone(),
+            })
+        );
+        // fully inside of the DRAM
+        assert!(mem
+            .add_device(0x8000_1000u32.into(), RamDevice::new(0x20))
+            .is_err());
+        // directly adjacent to the DRAM
+        assert_eq!(
+            mem.add_device(0x7FFF_FFF0u32.into(), RamDevice::new(0x10)),
+            Ok(())
+        );
+        assert_eq!(
+            mem.add_device(u64::MAX.into(), RamDevice::new(0x10)),
+            Err(MemoryMapError::OutOfRange {
+                base: u64::MAX.into(),
+                size: 0x10
+            })
+        );
+
+        assert_eq!(mem.regions().count(), 2);
+    }
+
+    #[test]
+    fn empty_devices() {
+        let mut mem = DeviceBus::new();
+        mem.write::<u32>(DRAM_BASE.into(), 0x1234).unwrap();
+
+        assert_eq!(
+            mem.add_device(DRAM_BASE.into(), RamDevice::new(0)),
+            Err(MemoryMapError::EmptyDevice(DRAM_BASE.into()))
+        );
+        assert_eq!(
+            mem.add_device(0x1000u32.into(), RamDevice::new(0)),
+            Err(MemoryMapError::EmptyDevice(0x1000u32.into()))
+        );
+        assert!(matches!(
+            mem.replace_device(DRAM_BASE.into(), RamDevice::new(0)),
+            Err(MemoryMapError::EmptyDevice(_))
+        ));
+
+        assert_eq!(mem.regions().count(), 1);
+        assert_eq!(mem.read::<u32>(DRAM_BASE.into()), Ok(0x1234));
+    }
+
+    #[test]
+    fn region_permissions() {
+        let mut mem = DeviceBus::new();
+        let rom = RamDevice::from_vec(vec![0x13, 0, 0, 0]);
+        mem.add_device_with(0x1000u32.into(), rom, Permissions::read_execute())
+            .unwrap();
+        mem.add_device_with(
+            0x2000u32.into(),
+            RamDevice::new(0x10),
+            Permissions::read_write(),
+        )
+        .unwrap();
+
+        assert_eq!(mem.fetch(0x1000u32.into()), Ok(0x13));
+        assert_eq!(mem.read::<u32>(0x1000u32.into()), Ok(0x13));
+        assert_eq!(
+            mem.write::<u32>(0x1000u32.into(), 0),
+            Err(Exception::StoreAccessFault(Address::from(0x1000u32)))
+        );
+
+        assert_eq!(mem.write::<u32>(0x2000u32.into(), 0x13), Ok(()));
+        assert_eq!(
+            mem.fetch(0x2000u32.into()),
+            Err(Exception::InstructionAccessFault(Address::from(0x2000u32)))
+        );
+
+        assert_eq!(
+            mem.fetch(0x3000u32.into()),
+            Err(Exception::InstructionAccessFault(Address::from(0x3000u32)))
+        );
+        assert_eq!(
+            mem.fetch(0x1002u32.into()),
+            Err(Exception::InstructionAddressMisaligned(0x1002u32.into()))
+        );
+    }
+
+    #[test]
+    fn replace_and_remove_devices() {
+        let mut mem = DeviceBus::new();
+        mem.write::<u32>(DRAM_BASE.into(), 0x1234).unwrap();
+
+        let old = mem
+            .replace_device(DRAM_BASE.into(), RamDevice::new(0x1000))
+            .unwrap();
+        assert_eq!(old.size(), DEFAULT_MEMORY_SIZE as u64);
+        assert_eq!(mem.read::<u32>(DRAM_BASE.into()), Ok(0));
+
+        assert!(mem.remove_device(DRAM_BASE.into()).is_some());
+        assert_eq!(
+            mem.read::<u32>(DRAM_BASE.into()),
+            Err(Exception::LoadAccessFault(Address::from(DRAM_BASE)))
+        );
+        assert!(matches!(
+            mem.replace_device(DRAM_BASE.into(), RamDevice::new(0x1000)),
+            Err(MemoryMapError::NoDevice(_))
+        ));
+    }
 }
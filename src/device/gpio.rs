@@ -0,0 +1,186 @@
+use super::{Device, Exception, Result};
+use alloc::{boxed::Box, rc::Rc};
+use core::cell::RefCell;
+
+/// The number of pins of the GPIO controller.
+const PINS: u8 = 32;
+
+/// Register offsets of the GPIO controller.
+mod reg {
+    pub const INPUT_VAL: u64 = 0x00;
+    pub const INPUT_EN: u64 = 0x04;
+    pub const OUTPUT_EN: u64 = 0x08;
+    pub const OUTPUT_VAL: u64 = 0x0C;
+    pub const OUT_XOR: u64 = 0x40;
+}
+
+/// A GPIO controller with 32 pins and a register layout compatible to the SiFive GPIO
+/// controller (without interrupts and pin configuration).
+///
+/// The state of the pins can be observed from the host, either by registering a callback
+/// using [`GpioController::on_change`] or by polling a [`GpioHandle`]. This makes
+/// e.g. blinking LEDs visible under emulation.
+pub struct GpioController {
+    state: Rc<RefCell<GpioState>>,
+    on_change: Option<Box<dyn FnMut(u32, u32)>>,
+}
+
+/// A handle to the pins of a [`GpioController`], that can be used after the controller
+/// was moved into a bus.
+#[derive(Clone)]
+pub struct GpioHandle {
+    state: Rc<RefCell<GpioState>>,
+}
+
+#[derive(Default)]
+struct GpioState {
+    /// The level of the input pins, as driven by the host.
+    inputs: u32,
+    input_en: u32,
+    output_en: u32,
+    output_val: u32,
+    out_xor: u32,
+}
+
+impl GpioState {
+    fn outputs(&self) -> u32 {
+        (self.output_val ^ self.out_xor) & self.output_en
+    }
+}
+
+impl GpioController {
+    /// Create a new GPIO controller with all pins disabled.
+    pub fn new() -> Self {
+        Self {
+            state: Rc::default(),
+            on_change: None,
+        }
+    }
+
+    /// Call `f` with the old and the new output levels, whenever the output level
+    /// of any pin changes.
+    pub fn on_change(mut self, f: impl FnMut(u32, u32) + 'static) -> Self {
+        self.on_change = Some(Box::new(f));
+        self
+    }
+
+    fn notify(&mut self, old: u32, new: u32) {
+        if old != new {
+            if let Some(f) = &mut self.on_change {
+                f(old, new);
+            }
+        }
+    }
+
+    /// Return a handle that can be used to access the pins of this controller.
+    pub fn handle(&self) -> GpioHandle {
+        GpioHandle {
+            state: Rc::clone(&self.state),
+        }
+    }
+}
+
+impl Default for GpioController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GpioHandle {
+    /// Return the output level of all pins. Pins that are not enabled as outputs are low.
+    pub fn outputs(&self) -> u32 {
+        self.state.borrow().outputs()
+    }
+
+    /// Return the output level of pin `n`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is not a valid pin number, i.e. not in `0..32`.
+    pub fn output(&self, n: u8) -> bool {
+        assert!(n < PINS, "invalid pin: {}", n);
+        self.outputs() & (1 << n) != 0
+    }
+
+    /// Drive the input level of pin `n`.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is not a valid pin number, i.e. not in `0..32`.
+    pub fn set_input(&self, n: u8, high: bool) {
+        assert!(n < PINS, "invalid pin: {}", n);
+        let mut state = self.state.borrow_mut();
+        if high {
+            state.inputs |= 1 << n;
+        } else {
+            state.inputs &= !(1 << n);
+        }
+    }
+}
+
+impl Device for GpioController {
+    fn size(&self) -> u64 {
+        0x100
+    }
+
+    fn kind(&self) -> &'static str {
+        "gpio"
+    }
+
+    fn load(&self, off: u64, buf: &mut [u8]) -> Result<()> {
+        if buf.len() != 4 {
+            return Err(Exception::LoadAccessFault(off.into()));
+        }
+
+        let state = self.state.borrow();
+        let val = match off {
+            reg::INPUT_VAL => state.inputs & state.input_en,
+            reg::INPUT_EN => state.input_en,
+            reg::OUTPUT_EN => state.output_en,
+            reg::OUTPUT_VAL => state.output_val,
+            reg::OUT_XOR => state.out_xor,
+            _ => 0,
+        };
+
+        buf.copy_from_slice(&val.to_le_bytes());
+        Ok(())
+    }
+
+    fn write(&mut self, off: u64, buf: &[u8]) -> Result<()> {
+        let val = match *buf {
+            [a, b, c, d] => u32::from_le_bytes([a, b, c, d]),
+            _ => return Err(Exception::StoreAccessFault(off.into())),
+        };
+
+        let (old, new) = {
+            let mut state = self.state.borrow_mut();
+            let old = state.outputs();
+            match off {
+                reg::INPUT_EN => state.input_en = val,
+                reg::OUTPUT_EN => state.output_en = val,
+                reg::OUTPUT_VAL => state.output_val = val,
+                reg::OUT_XOR => state.out_xor = val,
+                _ => {}
+            }
+            (old, state.outputs())
+        };
+
+        self.notify(old, new);
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        let (old, new) = {
+            let mut state = self.state.borrow_mut();
+            let old = state.outputs();
+            // the input levels are driven by the host, and thus survive a reset
+            *state = GpioState {
+                inputs: state.inputs,
+                ..GpioState::default()
+            };
+            (old, state.outputs())
+        };
+
+        self.notify(old, new);
+    }
+}
@@ -0,0 +1,431 @@
+use super::{Device, DeviceBus, MemoryMapError, Permissions, RamDevice, DRAM_BASE, PAGE_SIZE};
+use crate::{trap::Exception, Address};
+use alloc::{vec, vec::Vec};
+use core::fmt;
+use object::{
+    elf::{self, FileHeader32, FileHeader64},
+    read::elf::{Dyn, FileHeader, ProgramHeader, Rela},
+    Endianness, FileKind,
+};
+
+/// Errors that can occur while loading an object file into a [`DeviceBus`].
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be parsed.
+    Object(object::Error),
+    /// The file is not an ELF file.
+    UnsupportedFormat,
+    /// The file is a big endian ELF file.
+    BigEndian,
+    /// The file is not built for RISC-V, but for the given `e_machine`.
+    UnsupportedArchitecture(u16),
+    /// The file contents of the segment at the given address are out of bounds of the file.
+    InvalidSegment(Address),
+    /// The segment at the given address does not fit into the device that contains its start.
+    SegmentOutOfBounds {
+        /// The address of the segment.
+        addr: Address,
+        /// The size of the segment in memory.
+        size: u64,
+    },
+    /// Mapping a new RAM device for a segment failed.
+    Map(MemoryMapError),
+    /// Writing a segment into the device failed.
+    Access(Exception),
+    /// A load address was requested for an executable that is not position independent.
+    NotRelocatable,
+    /// The dynamic relocation table is not part of any loaded segment.
+    InvalidRelocations,
+    /// The file contains a dynamic relocation of the given type, which is not supported.
+    UnsupportedRelocation(u32),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Object(err) => write!(f, "failed to parse object file: {}", err),
+            LoadError::UnsupportedFormat => write!(f, "only ELF files are supported"),
+            LoadError::BigEndian => write!(f, "big endian ELF files are not supported"),
+            LoadError::UnsupportedArchitecture(machine) => {
+                write!(
+                    f,
+                    "ELF file is not built for RISC-V (e_machine {})",
+                    machine
+                )
+            }
+            LoadError::InvalidSegment(addr) => write!(
+                f,
+                "segment at {:#x} has invalid file offsets",
+                u64::from(*addr)
+            ),
+            LoadError::SegmentOutOfBounds { addr, size } => write!(
+                f,
+                "segment at {:#x} with size {:#x} does not fit into a single device",
+                u64::from(*addr),
+                size
+            ),
+            LoadError::Map(err) => write!(f, "failed to map memory for segment: {}", err),
+            LoadError::Access(err) => write!(f, "failed to write segment: {:?}", err),
+            LoadError::NotRelocatable => {
+                write!(f, "only position independent executables can be relocated")
+            }
+            LoadError::InvalidRelocations => write!(f, "invalid dynamic relocation table"),
+            LoadError::UnsupportedRelocation(ty) => {
+                write!(f, "unsupported dynamic relocation type {}", ty)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {}
+
+impl From<object::Error> for LoadError {
+    fn from(err: object::Error) -> Self {
+        LoadError::Object(err)
+    }
+}
+
+impl DeviceBus {
+    /// The maximum size of a RAM device that is mapped for the segments of an object file.
+    ///
+    /// The sizes come straight from the file, so they are limited to not allocate
+    /// arbitrary amounts of memory for a malicious file.
+    pub const MAX_SEGMENT_SIZE: u64 = 1 << 30;
+
+    /// Load the raw ELF file `data` into this bus.
+    ///
+    /// Only `PT_LOAD` segments are loaded, and the part of each segment that is not part of the
+    /// file (e.g. `.bss`) is filled with zeros. Segments that are not covered by any device
+    /// get a new RAM device, with the permissions of the segment flags, which may be at most
+    /// [`DeviceBus::MAX_SEGMENT_SIZE`] bytes large. If loading fails, these devices are
+    /// removed again.
+    ///
+    /// Position independent executables are loaded at [`DRAM_BASE`],
+    /// use [`DeviceBus::load_object_at`] to choose a different address.
+    ///
+    /// # Returns
+    ///
+    /// The entry point of the ELF file.
+    pub fn load_object(&mut self, data: &[u8]) -> Result<Address, LoadError> {
+        self.load_object_with(data, None)
+    }
+
+    /// Load the raw ELF file `data`, which must be a position independent executable,
+    /// into this bus at the given `base` address.
+    ///
+    /// All segments are moved by `base` and the `R_RISCV_RELATIVE` relocations are applied.
+    ///
+    /// # Returns
+    ///
+    /// The relocated entry point of the ELF file.
+    pub fn load_object_at(&mut self, data: &[u8], base: Address) -> Result<Address, LoadError> {
+        self.load_object_with(data, Some(u64::from(base)))
+    }
+
+    fn load_object_with(&mut self, data: &[u8], base: Option<u64>) -> Result<Address, LoadError> {
+        match FileKind::parse(data)? {
+            FileKind::Elf32 => self.load_elf::<FileHeader32<Endianness>>(data, base),
+            FileKind::Elf64 => self.load_elf::<FileHeader64<Endianness>>(data, base),
+            _ => Err(LoadError::UnsupportedFormat),
+        }
+    }
+
+    fn load_elf<Elf: FileHeader<Endian = Endianness>>(
+        &mut self,
+        data: &[u8],
+        base: Option<u64>,
+    ) -> Result<Address, LoadError> {
+        let header = Elf::parse(data)?;
+        let endian = header.endian()?;
+
+        if !header.is_little_endian() {
+            return Err(LoadError::BigEndian);
+        }
+        if header.e_machine(endian) != elf::EM_RISCV {
+            return Err(LoadError::UnsupportedArchitecture(header.e_machine(endian)));
+        }
+
+        // the offset that is added to every address of the file
+        let bias = match (header.e_type(endian), base) {
+            (elf::ET_DYN, base) => base.unwrap_or(DRAM_BASE),
+            (_, None) => 0,
+            (_, Some(_)) => return Err(LoadError::NotRelocatable),
+        };
+
+        // the regions that were mapped for the segments of this file
+        let mut mapped = Vec::new();
+        let result = self
+            .load_segments::<Elf>(header, data, bias, &mut mapped)
+            .and_then(|()| match bias {
+                0 => Ok(()),
+                bias => self.relocate::<Elf>(header, data, bias),
+            });
+
+        // don't leave the memory of a partially loaded file behind
+        if let Err(err) = result {
+            for base in mapped {
+                self.remove_device(base);
+            }
+            return Err(err);
+        }
+
+        let entry: u64 = header.e_entry(endian).into();
+        Ok(entry.wrapping_add(bias).into())
+    }
+
+    /// Load the `PT_LOAD` segments of the ELF file `data`, moved by `bias`, and push the base of
+    /// every region that is mapped for them to `mapped`.
+    fn load_segments<Elf: FileHeader<Endian = Endianness>>(
+        &mut self,
+        header: &Elf,
+        data: &[u8],
+        bias: u64,
+        mapped: &mut Vec<Address>,
+    ) -> Result<(), LoadError> {
+        let endian = header.endian()?;
+        let segments = header.program_headers(endian, data)?;
+        for seg in segments
+            .iter()
+            .filter(|seg| seg.p_type(endian) == elf::PT_LOAD)
+        {
+            let vaddr: u64 = seg.p_vaddr(endian).into();
+            let addr = vaddr.wrapping_add(bias);
+            let size: u64 = seg.p_memsz(endian).into();
+            let file_data = seg
+                .data(endian, data)
+                .map_err(|()| LoadError::InvalidSegment(addr.into()))?;
+
+            if size == 0 {
+                continue;
+            }
+
+            let flags = seg.p_flags(endian);
+            let permissions = Permissions {
+                read: flags & elf::PF_R != 0,
+                write: flags & elf::PF_W != 0,
+                execute: flags & elf::PF_X != 0,
+            };
+
+            if self.region_for(addr.into(), 1).is_none() {
+                let base = self.map_segment(addr, size, permissions)?;
+                mapped.push(base);
+            }
+
+            let base = *self
+                .region_for(addr.into(), 1)
+                .expect("segment was mapped above")
+                .0;
+            let offset = addr - u64::from(base);
+            let out_of_bounds = LoadError::SegmentOutOfBounds {
+                addr: addr.into(),
+                size,
+            };
+            let end = match offset.checked_add(size) {
+                Some(end) if file_data.len() as u64 <= size => end,
+                _ => return Err(out_of_bounds),
+            };
+
+            // a segment may start inside the region of a previous segment, but extend past it
+            if mapped.contains(&base) {
+                self.grow_region(base, end)?;
+            }
+
+            let region = self.devices.get_mut(&base).expect("region was found above");
+            if end > region.dev.size() {
+                return Err(out_of_bounds);
+            }
+
+            // a page that is shared by segments of this file must allow all their accesses
+            if mapped.contains(&base) {
+                let perms = &mut region.permissions;
+                perms.read |= permissions.read;
+                perms.write |= permissions.write;
+                perms.execute |= permissions.execute;
+            }
+
+            // write the segment straight from the file, and zero the rest of it in chunks,
+            // so loading doesn't need to copy the segment into a temporary buffer
+            region
+                .dev
+                .write(offset, file_data)
+                .map_err(LoadError::Access)?;
+
+            const ZEROES: [u8; 512] = [0; 512];
+            let mut zero_off = file_data.len() as u64;
+            while zero_off < size {
+                let len = (size - zero_off).min(ZEROES.len() as u64);
+                region
+                    .dev
+                    .write(offset + zero_off, &ZEROES[..len as usize])
+                    .map_err(LoadError::Access)?;
+                zero_off += len;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the dynamic relocations of a position independent executable, that was loaded
+    /// with the given `bias`.
+    fn relocate<Elf: FileHeader<Endian = Endianness>>(
+        &mut self,
+        header: &Elf,
+        data: &[u8],
+        bias: u64,
+    ) -> Result<(), LoadError> {
+        let endian = header.endian()?;
+        let segments = header.program_headers(endian, data)?;
+
+        // find the location of the relocation table using the dynamic section
+        let dynamic = segments
+            .iter()
+            .find_map(|seg| seg.dynamic(endian, data).transpose())
+            .transpose()?
+            .unwrap_or(&[]);
+        let (mut rela, mut rela_size, mut rela_ent) = (None, 0, None);
+        for entry in dynamic {
+            let tag: u64 = entry.d_tag(endian).into();
+            let val: u64 = entry.d_val(endian).into();
+            match tag {
+                tag if tag == u64::from(elf::DT_RELA) => rela = Some(val),
+                tag if tag == u64::from(elf::DT_RELASZ) => rela_size = val,
+                tag if tag == u64::from(elf::DT_RELAENT) => rela_ent = Some(val),
+                _ => {}
+            }
+        }
+        let rela = match rela {
+            Some(rela) => rela,
+            None => return Ok(()),
+        };
+
+        // the entries are parsed as `Elf::Rela`, so they must have exactly that size
+        let entry_size = core::mem::size_of::<Elf::Rela>() as u64;
+        if matches!(rela_ent, Some(size) if size != entry_size) {
+            return Err(LoadError::InvalidRelocations);
+        }
+
+        // the table is addressed by its virtual address, so find the segment that contains it
+        let table = segments
+            .iter()
+            .filter(|seg| seg.p_type(endian) == elf::PT_LOAD)
+            .find_map(|seg| seg.data_range(endian, data, rela, rela_size).transpose())
+            .transpose()
+            .map_err(|()| LoadError::InvalidRelocations)?
+            .ok_or(LoadError::InvalidRelocations)?;
+        let count = table.len() / core::mem::size_of::<Elf::Rela>();
+        let (relocs, _) = object::slice_from_bytes::<Elf::Rela>(table, count)
+            .map_err(|()| LoadError::InvalidRelocations)?;
+
+        for reloc in relocs {
+            let offset: u64 = reloc.r_offset(endian).into();
+            let addend: i64 = reloc.r_addend(endian).into();
+            let addr = Address::from(offset.wrapping_add(bias));
+
+            let value = match reloc.r_type(endian, false) {
+                elf::R_RISCV_NONE => continue,
+                elf::R_RISCV_RELATIVE => bias.wrapping_add(addend as u64),
+                ty => return Err(LoadError::UnsupportedRelocation(ty)),
+            };
+
+            // write through the device directly, since relocations may target read-only memory
+            let (&base, region) = self
+                .region_for_mut(addr, if header.is_type_64() { 8 } else { 4 })
+                .ok_or(LoadError::Access(Exception::StoreAccessFault(addr)))?;
+            let offset = u64::from(addr) - u64::from(base);
+            let result = if header.is_type_64() {
+                region.dev.write(offset, &value.to_le_bytes())
+            } else {
+                region.dev.write(offset, &(value as u32).to_le_bytes())
+            };
+            result.map_err(LoadError::Access)?;
+        }
+
+        Ok(())
+    }
+
+    /// Map a new RAM device that covers the segment at `addr` with `size` bytes.
+    ///
+    /// The device is extended to page boundaries, as long as it does not overlap any other device.
+    fn map_segment(
+        &mut self,
+        addr: u64,
+        size: u64,
+        permissions: Permissions,
+    ) -> Result<Address, LoadError> {
+        let end = addr
+            .checked_add(size)
+            .ok_or(LoadError::SegmentOutOfBounds {
+                addr: addr.into(),
+                size,
+            })?;
+
+        // the end of the previous and the start of the next region limit the new region
+        let prev_end = self
+            .devices
+            .range(..Address::from(addr))
+            .next_back()
+            .map_or(0, |(&base, region)| u64::from(base) + region.dev.size());
+        let next_start = self
+            .devices
+            .range(Address::from(addr)..)
+            .next()
+            .map_or(u64::MAX, |(&base, _)| u64::from(base));
+
+        let start = (addr & !(PAGE_SIZE - 1)).max(prev_end);
+        let end = match end.checked_add(PAGE_SIZE - 1) {
+            Some(end) => (end & !(PAGE_SIZE - 1)).min(next_start),
+            None => end,
+        };
+
+        let ram = segment_ram(start, end.saturating_sub(start))?;
+        self.add_device_with(start.into(), ram, permissions)
+            .map_err(LoadError::Map)?;
+        Ok(start.into())
+    }
+
+    /// Grow the RAM device that was mapped at `base` by [`DeviceBus::map_segment`] to `size`
+    /// bytes, keeping its contents.
+    ///
+    /// Like a new device, it is extended to a page boundary, but never overlaps the next device,
+    /// so the device may still be smaller than `size` afterwards.
+    fn grow_region(&mut self, base: Address, size: u64) -> Result<(), LoadError> {
+        let start = u64::from(base);
+        let next_start = self
+            .devices
+            .range(base..)
+            .nth(1)
+            .map_or(u64::MAX, |(&base, _)| u64::from(base));
+        let end = match start
+            .checked_add(size)
+            .and_then(|end| end.checked_add(PAGE_SIZE - 1))
+        {
+            Some(end) => (end & !(PAGE_SIZE - 1)).min(next_start),
+            None => next_start,
+        };
+
+        let old = &self.devices[&base].dev;
+        if end - start <= old.size() {
+            return Ok(());
+        }
+
+        let mut contents = vec![0; old.size() as usize];
+        old.load(0, &mut contents).map_err(LoadError::Access)?;
+        let mut ram = segment_ram(start, end - start)?;
+        ram.write(0, &contents).map_err(LoadError::Access)?;
+        self.replace_device(base, ram).map_err(LoadError::Map)?;
+        Ok(())
+    }
+}
+
+/// Create a RAM device with `size` bytes for the region at `addr`, unless it is larger than
+/// [`DeviceBus::MAX_SEGMENT_SIZE`].
+fn segment_ram(addr: u64, size: u64) -> Result<RamDevice, LoadError> {
+    match usize::try_from(size) {
+        Ok(len) if size <= DeviceBus::MAX_SEGMENT_SIZE => Ok(RamDevice::new(len)),
+        _ => Err(LoadError::SegmentOutOfBounds {
+            addr: addr.into(),
+            size,
+        }),
+    }
+}
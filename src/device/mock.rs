@@ -0,0 +1,198 @@
+use super::{Device, Exception, MemoryPod, Result};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    rc::Rc,
+    vec::Vec,
+};
+use core::cell::RefCell;
+
+/// A [`Device`] that answers reads with scripted responses and checks writes against a list
+/// of expectations.
+///
+/// This allows testing driver code against deterministic MMIO behaviour, without implementing
+/// a real device model. Use [`MockDevice::handle`] to inspect the device after it was added
+/// to a bus.
+pub struct MockDevice {
+    size: u64,
+    state: Rc<RefCell<MockState>>,
+}
+
+/// A handle to the state of a [`MockDevice`], that can be used to inspect the device after
+/// it was moved into a bus.
+#[derive(Clone)]
+pub struct MockHandle {
+    state: Rc<RefCell<MockState>>,
+}
+
+#[derive(Default, Clone)]
+struct MockState {
+    /// Responses that are returned once, in order, for reads at an offset.
+    sequences: BTreeMap<u64, VecDeque<Vec<u8>>>,
+    /// Responses that are returned for every read at an offset, after the sequence is exhausted.
+    values: BTreeMap<u64, Vec<u8>>,
+    /// Writes that are expected to happen, in order.
+    expected: VecDeque<(u64, Vec<u8>)>,
+    /// Every write that was made to the device.
+    writes: Vec<(u64, Vec<u8>)>,
+    /// Accept writes that were not expected, instead of panicking.
+    record: bool,
+}
+
+impl MockDevice {
+    /// Create a new mock device that covers `size` bytes and has no responses or expectations.
+    pub fn new(size: u64) -> Self {
+        Self {
+            size,
+            state: Rc::default(),
+        }
+    }
+
+    /// Return `value` for every read of a `T` at offset `off`.
+    pub fn with_read<T: MemoryPod>(self, off: u64, value: T) -> Self {
+        self.state.borrow_mut().values.insert(off, to_bytes(value));
+        self
+    }
+
+    /// Return the given values in order for reads of a `T` at offset `off`.
+    ///
+    /// Once all values were read, reads fall back to the value set by
+    /// [`MockDevice::with_read`], or fault if there is none.
+    pub fn with_read_sequence<T: MemoryPod>(
+        self,
+        off: u64,
+        values: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.state
+            .borrow_mut()
+            .sequences
+            .entry(off)
+            .or_default()
+            .extend(values.into_iter().map(to_bytes));
+        self
+    }
+
+    /// Expect a write of `value` at offset `off`.
+    ///
+    /// Expected writes must happen in the order they were added, otherwise the device panics.
+    /// Unless the device is in record mode, any write after the expected ones panics as well.
+    pub fn expect_write<T: MemoryPod>(self, off: u64, value: T) -> Self {
+        self.state
+            .borrow_mut()
+            .expected
+            .push_back((off, to_bytes(value)));
+        self
+    }
+
+    /// Accept every write that is not covered by an expectation, and only record it,
+    /// so it can be inspected using [`MockHandle::writes`].
+    pub fn record_writes(self) -> Self {
+        self.state.borrow_mut().record = true;
+        self
+    }
+
+    /// Return a handle that can be used to inspect this device.
+    pub fn handle(&self) -> MockHandle {
+        MockHandle {
+            state: Rc::clone(&self.state),
+        }
+    }
+}
+
+impl MockHandle {
+    /// Return every write that was made to the device so far, as the offset and
+    /// the raw bytes in little endian.
+    pub fn writes(&self) -> Vec<(u64, Vec<u8>)> {
+        self.state.borrow().writes.clone()
+    }
+
+    /// Return the number of expected writes that did not happen yet.
+    pub fn pending_writes(&self) -> usize {
+        self.state.borrow().expected.len()
+    }
+
+    /// Assert that every expected write happened.
+    ///
+    /// # Panics
+    ///
+    /// If there are expected writes left.
+    pub fn assert_done(&self) {
+        let state = self.state.borrow();
+        if let Some((off, value)) = state.expected.front() {
+            panic!(
+                "{} expected writes did not happen, the next one is {:x?} at offset {:#x}",
+                state.expected.len(),
+                value,
+                off
+            );
+        }
+    }
+}
+
+impl Device for MockDevice {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn kind(&self) -> &'static str {
+        "mock"
+    }
+
+    fn load(&self, off: u64, buf: &mut [u8]) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+
+        // first try the next response in the sequence, then the fixed value
+        let from_sequence = state
+            .sequences
+            .get_mut(&off)
+            .and_then(|seq| match seq.front() {
+                Some(value) if value.len() == buf.len() => seq.pop_front(),
+                _ => None,
+            });
+        let value = from_sequence
+            .or_else(|| state.values.get(&off).cloned())
+            .filter(|value| value.len() == buf.len())
+            .ok_or(Exception::LoadAccessFault(off.into()))?;
+
+        buf.copy_from_slice(&value);
+        Ok(())
+    }
+
+    /// # Panics
+    ///
+    /// If the write does not match the next expected write, or there is no expected write
+    /// left and the device is not in record mode.
+    fn write(&mut self, off: u64, buf: &[u8]) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+
+        match state.expected.pop_front() {
+            Some((exp_off, exp_value)) => assert!(
+                exp_off == off && exp_value == buf,
+                "expected write of {:x?} at offset {:#x}, but got {:x?} at offset {:#x}",
+                exp_value,
+                exp_off,
+                buf,
+                off
+            ),
+            None => assert!(
+                state.record,
+                "unexpected write of {:x?} at offset {:#x}",
+                buf, off
+            ),
+        }
+
+        state.writes.push((off, buf.to_vec()));
+        Ok(())
+    }
+
+    fn fork(&self) -> Option<Box<dyn Device>> {
+        Some(Box::new(MockDevice {
+            size: self.size,
+            state: Rc::new(RefCell::new(self.state.borrow().clone())),
+        }))
+    }
+}
+
+fn to_bytes<T: MemoryPod>(value: T) -> Vec<u8> {
+    bytemuck::bytes_of(&value.process_write()).to_vec()
+}
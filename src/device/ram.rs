@@ -1,48 +1,128 @@
-use super::{Device, Exception, Result};
+use super::{page_chunks, single_page, Device, Exception, Page, Result, PAGE_SIZE};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 
 /// A [`Device`] which acts as a RAM module containing a fixed buffer of memory.
+///
+/// The memory is split into pages that are shared between clones of the device,
+/// and only copied once one of the clones writes to them.
+/// This makes cloning a RAM device cheap, no matter how big it is.
+///
+/// Every page that is written to is marked as dirty, until [`RamDevice::clear_dirty`]
+/// is called, so only changed pages need to be looked at, e.g. for incremental snapshots.
+#[derive(Clone)]
 pub struct RamDevice {
-    ram: Box<[u8]>,
+    size: u64,
+    pages: Box<[Arc<Page>]>,
+    /// A bitmap containing one bit for every page, that is set if the page is dirty.
+    dirty: Box<[u64]>,
 }
 
 impl RamDevice {
     /// Create a new RAM device that is able to hold `size` bytes of memory.
     pub fn new(size: usize) -> Self {
-        Self {
-            ram: vec![0u8; size].into_boxed_slice(),
-        }
+        // every page starts out as the same zero page, which is copied on the first write
+        let zero = Arc::new([0u8; PAGE_SIZE as usize]);
+        let pages = (0..size as u64)
+            .step_by(PAGE_SIZE as usize)
+            .map(|_| Arc::clone(&zero))
+            .collect();
+
+        Self::with_pages(size as u64, pages)
     }
 
     /// Create a RAM device that is initialized using the given vec.
     pub fn from_vec(vec: Vec<u8>) -> Self {
-        Self {
-            ram: vec.into_boxed_slice(),
-        }
+        let pages = vec
+            .chunks(PAGE_SIZE as usize)
+            .map(|chunk| {
+                let mut page = [0u8; PAGE_SIZE as usize];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+
+        Self::with_pages(vec.len() as u64, pages)
+    }
+
+    fn with_pages(size: u64, pages: Box<[Arc<Page>]>) -> Self {
+        let dirty = (0..pages.len()).step_by(64).map(|_| 0).collect();
+        Self { size, pages, dirty }
+    }
+
+    /// Check if the page with the given number was written to since the last
+    /// call to [`RamDevice::clear_dirty`].
+    pub fn is_dirty(&self, page: u64) -> bool {
+        matches!(self.dirty.get((page / 64) as usize), Some(word) if word & (1 << (page % 64)) != 0)
+    }
+
+    /// Return the numbers of all dirty pages, in ascending order.
+    ///
+    /// The page number multiplied by [`PAGE_SIZE`] is the offset of the page inside this device.
+    pub fn dirty_pages(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.pages.len() as u64).filter(move |&page| self.is_dirty(page))
+    }
+
+    /// Mark all pages as clean.
+    pub fn clear_dirty(&mut self) {
+        self.dirty.iter_mut().for_each(|word| *word = 0);
+    }
+
+    fn page_mut(&mut self, page: u64) -> &mut Page {
+        self.dirty[(page / 64) as usize] |= 1 << (page % 64);
+        Arc::make_mut(&mut self.pages[page as usize])
+    }
+
+    fn in_bounds(&self, off: u64, len: usize) -> bool {
+        matches!(off.checked_add(len as u64), Some(end) if end <= self.size)
     }
 }
 
 impl Device for RamDevice {
     fn size(&self) -> u64 {
-        self.ram.len() as u64
+        self.size
+    }
+
+    fn kind(&self) -> &'static str {
+        "ram"
     }
 
     fn load(&self, off: u64, buf: &mut [u8]) -> Result<()> {
-        let addr = off as usize;
-        if let Some(from) = self.ram.get(addr..addr + buf.len()) {
-            buf.copy_from_slice(from);
-            Ok(())
-        } else {
-            Err(Exception::LoadAccessFault)
+        if !self.in_bounds(off, buf.len()) {
+            return Err(Exception::LoadAccessFault(off.into()));
         }
+
+        for (page, page_off, range) in page_chunks(off, buf.len()) {
+            let to = &mut buf[range];
+            let page = &self.pages[page as usize];
+            to.copy_from_slice(&page[page_off..page_off + to.len()]);
+        }
+        Ok(())
     }
 
     fn write(&mut self, off: u64, buf: &[u8]) -> Result<()> {
-        let addr = off as usize;
-        if let Some(to) = self.ram.get_mut(addr..addr + buf.len()) {
-            to.copy_from_slice(buf);
-            Ok(())
-        } else {
-            Err(Exception::StoreAccessFault)
+        if !self.in_bounds(off, buf.len()) {
+            return Err(Exception::StoreAccessFault(off.into()));
+        }
+
+        for (page, page_off, range) in page_chunks(off, buf.len()) {
+            let from = &buf[range];
+            let page = self.page_mut(page);
+            page[page_off..page_off + from.len()].copy_from_slice(from);
         }
+        Ok(())
+    }
+
+    fn slice(&self, off: u64, len: usize) -> Option<&[u8]> {
+        let (page, range) = single_page(off, len).filter(|_| self.in_bounds(off, len))?;
+        Some(&self.pages[page as usize][range])
+    }
+
+    fn slice_mut(&mut self, off: u64, len: usize) -> Option<&mut [u8]> {
+        let (page, range) = single_page(off, len).filter(|_| self.in_bounds(off, len))?;
+        Some(&mut self.page_mut(page)[range])
+    }
+
+    fn fork(&self) -> Option<Box<dyn Device>> {
+        Some(Box::new(self.clone()))
     }
 }
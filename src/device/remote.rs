@@ -0,0 +1,194 @@
+//! Devices that are implemented in another process.
+//!
+//! The bus side and the device side exchange messages over any byte stream, e.g. a
+//! socket or a pair of pipes. All integers are little endian.
+//!
+//! Every request starts with a single byte opcode, followed by the offset inside the
+//! device as `u64` and the length of the access as `u32`:
+//!
+//! - `0x00` (load): the device responds with a status byte, followed by `len` bytes
+//!   of data if the status is zero.
+//! - `0x01` (write): the request is followed by `len` bytes of data, and the device
+//!   responds with a status byte.
+//! - `0x02` (interrupts): the offset and length are ignored, and the device responds
+//!   with a status byte, followed by the asserted interrupt lines as `u32` bitmask if the
+//!   status is zero.
+//!
+//! A status of zero means success, anything else raises an access fault. Accesses
+//! that are not fully contained in the device, or longer than [`MAX_ACCESS`] bytes,
+//! always fail.
+
+use super::{Device, Exception, Result};
+use core::cell::RefCell;
+use std::io::{self, Read, Write};
+
+const OP_LOAD: u8 = 0x00;
+const OP_WRITE: u8 = 0x01;
+const OP_INTERRUPTS: u8 = 0x02;
+
+/// The maximum number of bytes a single request may access.
+pub const MAX_ACCESS: u32 = 0x1000;
+
+const STATUS_OK: u8 = 0x00;
+const STATUS_FAULT: u8 = 0x01;
+
+/// A [`Device`] that forwards every access to a device in another process, using the
+/// protocol described in the [module documentation](self).
+///
+/// Any I/O error while talking to the device is reported as an access fault.
+pub struct RemoteDevice<S> {
+    size: u64,
+    stream: RefCell<S>,
+}
+
+impl<S: Read + Write> RemoteDevice<S> {
+    /// Create a new device with `size` bytes, that talks to the remote device using `stream`.
+    pub fn new(size: u64, stream: S) -> Self {
+        Self {
+            size,
+            stream: RefCell::new(stream),
+        }
+    }
+
+    /// Ask the remote device which of its interrupt lines are asserted.
+    ///
+    /// Returns a bitmask with a bit set for every asserted line, which an interrupt
+    /// controller can poll.
+    pub fn interrupts(&self) -> io::Result<u32> {
+        match self.request(OP_INTERRUPTS, 0, 0, &[])? {
+            STATUS_OK => {
+                let mut lines = [0u8; 4];
+                self.stream.borrow_mut().read_exact(&mut lines)?;
+                Ok(u32::from_le_bytes(lines))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "remote device failed to report interrupts",
+            )),
+        }
+    }
+
+    /// Consume the device and return the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    fn request(&self, op: u8, off: u64, len: usize, data: &[u8]) -> io::Result<u8> {
+        let mut stream = self.stream.borrow_mut();
+
+        let mut header = [0u8; 13];
+        header[0] = op;
+        header[1..9].copy_from_slice(&off.to_le_bytes());
+        header[9..].copy_from_slice(&(len as u32).to_le_bytes());
+        stream.write_all(&header)?;
+        stream.write_all(data)?;
+        stream.flush()?;
+
+        let mut status = [0u8];
+        stream.read_exact(&mut status)?;
+        Ok(status[0])
+    }
+}
+
+impl<S: Read + Write> Device for RemoteDevice<S> {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn kind(&self) -> &'static str {
+        "remote"
+    }
+
+    fn load(&self, off: u64, buf: &mut [u8]) -> Result<()> {
+        match self.request(OP_LOAD, off, buf.len(), &[]) {
+            Ok(STATUS_OK) => self
+                .stream
+                .borrow_mut()
+                .read_exact(buf)
+                .map_err(|_| Exception::LoadAccessFault(off.into())),
+            _ => Err(Exception::LoadAccessFault(off.into())),
+        }
+    }
+
+    fn write(&mut self, off: u64, buf: &[u8]) -> Result<()> {
+        match self.request(OP_WRITE, off, buf.len(), buf) {
+            Ok(STATUS_OK) => Ok(()),
+            _ => Err(Exception::StoreAccessFault(off.into())),
+        }
+    }
+}
+
+/// Serve the requests of a [`RemoteDevice`] on the other end of `stream` using `dev`,
+/// until the stream is closed.
+///
+/// This is the device side of the protocol, which allows running any [`Device`]
+/// in a separate process. The device never asserts any interrupts, use
+/// [`serve_with_interrupts`] for devices that do.
+pub fn serve(stream: impl Read + Write, dev: &mut dyn Device) -> io::Result<()> {
+    serve_with_interrupts(stream, dev, || 0)
+}
+
+/// Serve the requests of a [`RemoteDevice`] like [`serve`], and report the interrupt
+/// lines returned by `interrupts` as asserted.
+pub fn serve_with_interrupts(
+    mut stream: impl Read + Write,
+    dev: &mut dyn Device,
+    mut interrupts: impl FnMut() -> u32,
+) -> io::Result<()> {
+    let mut buf = std::vec::Vec::new();
+
+    loop {
+        let mut header = [0u8; 13];
+        match stream.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+
+        let mut off = [0u8; 8];
+        off.copy_from_slice(&header[1..9]);
+        let off = u64::from_le_bytes(off);
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&header[9..]);
+        let len = u32::from_le_bytes(len);
+
+        let valid = len <= MAX_ACCESS
+            && matches!(off.checked_add(u64::from(len)), Some(end) if end <= dev.size());
+        buf.resize(if valid { len as usize } else { 0 }, 0);
+
+        match header[0] {
+            OP_INTERRUPTS => {
+                stream.write_all(&[STATUS_OK])?;
+                stream.write_all(&interrupts().to_le_bytes())?;
+            }
+            OP_LOAD if !valid => stream.write_all(&[STATUS_FAULT])?,
+            OP_WRITE if !valid => {
+                // skip the payload to stay in sync with the other side
+                io::copy(&mut (&mut stream).take(u64::from(len)), &mut io::sink())?;
+                stream.write_all(&[STATUS_FAULT])?;
+            }
+            OP_LOAD => match dev.load(off, &mut buf) {
+                Ok(()) => {
+                    stream.write_all(&[STATUS_OK])?;
+                    stream.write_all(&buf)?;
+                }
+                Err(_) => stream.write_all(&[STATUS_FAULT])?,
+            },
+            OP_WRITE => {
+                stream.read_exact(&mut buf)?;
+                let status = match dev.write(off, &buf) {
+                    Ok(()) => STATUS_OK,
+                    Err(_) => STATUS_FAULT,
+                };
+                stream.write_all(&[status])?;
+            }
+            op => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    std::format!("invalid opcode: {:#04x}", op),
+                ))
+            }
+        }
+        stream.flush()?;
+    }
+}
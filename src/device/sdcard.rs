@@ -0,0 +1,223 @@
+use super::spi::SpiDevice;
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+/// The size of a single block on the card.
+pub const BLOCK_SIZE: usize = 512;
+
+/// Token that starts a single block of data.
+const START_TOKEN: u8 = 0xFE;
+
+/// Data response token for accepted data.
+const DATA_ACCEPTED: u8 = 0x05;
+
+/// Bits of the R1 response.
+mod r1 {
+    pub const READY: u8 = 0x00;
+    pub const IDLE: u8 = 0x01;
+    pub const ILLEGAL_COMMAND: u8 = 0x04;
+    pub const ADDRESS_ERROR: u8 = 0x20;
+    pub const PARAMETER_ERROR: u8 = 0x40;
+}
+
+/// The OCR register of a powered up, high capacity card supporting 2.7-3.6V.
+const OCR: u32 = 0xC0FF_8000;
+
+/// What the card is currently receiving from the host.
+enum Receive {
+    /// Waiting for the start of a command frame.
+    Command,
+    /// Receiving the remaining bytes of a command frame.
+    Frame(Vec<u8>),
+    /// Waiting for the start token of a block that is written to `block`.
+    WriteToken { block: usize },
+    /// Receiving the data and CRC of a block that is written to `block`.
+    WriteData { block: usize, data: Vec<u8> },
+}
+
+/// An SD card in SPI mode, backed by an in-memory disk image.
+///
+/// The card behaves like a high capacity card (SDHC), so all addresses are block addresses.
+/// Supported are the commands to initialize the card (CMD0, CMD8, CMD55 + ACMD41, CMD58),
+/// CMD16 and reading/writing single blocks (CMD17, CMD24). CRCs are never checked.
+pub struct SdCard {
+    image: Vec<u8>,
+    idle: bool,
+    app_cmd: bool,
+    receive: Receive,
+    response: VecDeque<u8>,
+}
+
+impl SdCard {
+    /// Create a new SD card that uses `image` as its content.
+    ///
+    /// The image is padded with zeros to a multiple of the block size.
+    pub fn new(mut image: Vec<u8>) -> Self {
+        let padding = (BLOCK_SIZE - image.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        image.resize(image.len() + padding, 0);
+
+        Self {
+            image,
+            idle: true,
+            app_cmd: false,
+            receive: Receive::Command,
+            response: VecDeque::new(),
+        }
+    }
+
+    /// Create a new SD card using the content of the image file at `path`.
+    #[cfg(feature = "std")]
+    pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        std::fs::read(path).map(Self::new)
+    }
+
+    /// Return the current content of the card.
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// Consume the card and return its content, e.g. to write it back to a file.
+    pub fn into_image(self) -> Vec<u8> {
+        self.image
+    }
+
+    fn block_count(&self) -> usize {
+        self.image.len() / BLOCK_SIZE
+    }
+
+    fn r1(&self, flags: u8) -> u8 {
+        flags | if self.idle { r1::IDLE } else { r1::READY }
+    }
+
+    fn respond(&mut self, bytes: &[u8]) {
+        // the response is sent one byte after the command (NCR)
+        self.response.push_back(0xFF);
+        self.response.extend(bytes);
+    }
+
+    fn command(&mut self, cmd: u8, arg: u32) {
+        let app_cmd = core::mem::replace(&mut self.app_cmd, false);
+
+        match (app_cmd, cmd) {
+            // GO_IDLE_STATE
+            (_, 0) => {
+                self.idle = true;
+                self.respond(&[r1::IDLE]);
+            }
+            // SEND_IF_COND, echo the voltage and check pattern
+            (_, 8) => {
+                let [_, _, voltage, pattern] = arg.to_be_bytes();
+                self.respond(&[self.r1(0), 0x00, 0x00, voltage & 0xF, pattern]);
+            }
+            // APP_CMD
+            (_, 55) => {
+                self.app_cmd = true;
+                self.respond(&[self.r1(0)]);
+            }
+            // SD_SEND_OP_COND
+            (true, 41) => {
+                self.idle = false;
+                self.respond(&[r1::READY]);
+            }
+            // READ_OCR
+            (_, 58) => {
+                let [a, b, c, d] = OCR.to_be_bytes();
+                self.respond(&[self.r1(0), a, b, c, d]);
+            }
+            // any other command is illegal until the card is initialized
+            _ if self.idle => self.respond(&[self.r1(r1::ILLEGAL_COMMAND)]),
+            // SET_BLOCKLEN, only the fixed block size is supported
+            (_, 16) if arg as usize == BLOCK_SIZE => self.respond(&[r1::READY]),
+            (_, 16) => self.respond(&[r1::PARAMETER_ERROR]),
+            // READ_SINGLE_BLOCK
+            (_, 17) => match self.block_range(arg) {
+                Some(range) => {
+                    self.respond(&[r1::READY, 0xFF, START_TOKEN]);
+                    self.response.extend(&self.image[range]);
+                    // the CRC is never checked, so just send zeros
+                    self.response.extend(&[0, 0]);
+                }
+                None => self.respond(&[r1::ADDRESS_ERROR]),
+            },
+            // WRITE_BLOCK
+            (_, 24) => match self.block_range(arg) {
+                Some(_) => {
+                    self.respond(&[r1::READY]);
+                    self.receive = Receive::WriteToken {
+                        block: arg as usize,
+                    };
+                }
+                None => self.respond(&[r1::ADDRESS_ERROR]),
+            },
+            _ => self.respond(&[self.r1(r1::ILLEGAL_COMMAND)]),
+        }
+    }
+
+    fn block_range(&self, block: u32) -> Option<core::ops::Range<usize>> {
+        let block = block as usize;
+        if block < self.block_count() {
+            Some(block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE)
+        } else {
+            None
+        }
+    }
+
+    fn receive(&mut self, mosi: u8) {
+        self.receive = match core::mem::replace(&mut self.receive, Receive::Command) {
+            // commands start with the bits `01`
+            Receive::Command if mosi & 0xC0 == 0x40 => Receive::Frame(vec![mosi]),
+            Receive::Command => Receive::Command,
+            Receive::Frame(mut frame) => {
+                frame.push(mosi);
+                if frame.len() < 6 {
+                    Receive::Frame(frame)
+                } else {
+                    let arg = u32::from_be_bytes([frame[1], frame[2], frame[3], frame[4]]);
+                    self.command(frame[0] & 0x3F, arg);
+                    // the command may have changed what to receive next
+                    return;
+                }
+            }
+            Receive::WriteToken { block } if mosi == START_TOKEN => Receive::WriteData {
+                block,
+                data: Vec::with_capacity(BLOCK_SIZE + 2),
+            },
+            Receive::WriteToken { block } => Receive::WriteToken { block },
+            Receive::WriteData { block, mut data } => {
+                data.push(mosi);
+                if data.len() < BLOCK_SIZE + 2 {
+                    Receive::WriteData { block, data }
+                } else {
+                    let start = block * BLOCK_SIZE;
+                    self.image[start..start + BLOCK_SIZE].copy_from_slice(&data[..BLOCK_SIZE]);
+                    // the card is busy for a single byte after accepting the data
+                    self.response.extend(&[DATA_ACCEPTED, 0x00]);
+                    Receive::Command
+                }
+            }
+        };
+    }
+}
+
+impl SpiDevice for SdCard {
+    fn transfer(&mut self, mosi: u8) -> u8 {
+        let miso = self.response.pop_front().unwrap_or(0xFF);
+        self.receive(mosi);
+        miso
+    }
+
+    fn select(&mut self, selected: bool) {
+        // deselecting the card aborts any transfer in progress
+        if !selected {
+            self.receive = Receive::Command;
+            self.response.clear();
+        }
+    }
+
+    fn reset(&mut self) {
+        // a power cycle puts the card back into SD mode, so it has to be initialized again
+        self.idle = true;
+        self.app_cmd = false;
+        self.receive = Receive::Command;
+        self.response.clear();
+    }
+}
@@ -0,0 +1,193 @@
+use super::{Device, Exception, Result, PAGE_SIZE};
+use alloc::{boxed::Box, rc::Rc, vec, vec::Vec};
+use core::{cell::RefCell, ops::Range};
+
+/// Register offsets of the shared memory device.
+mod reg {
+    /// Written by the guest to notify the host.
+    pub const DOORBELL: u64 = 0x00;
+    /// Bits set by the host to notify the guest, cleared by writing ones to them.
+    pub const PENDING: u64 = 0x04;
+    /// The size of the shared buffer in bytes.
+    pub const SIZE: u64 = 0x08;
+}
+
+/// A device that exposes a buffer, which is shared with the host, to the guest.
+///
+/// The first page contains the registers, and the buffer starts at the second page.
+/// The guest notifies the host by writing to the doorbell register, which calls the
+/// callback registered using [`SharedMemory::on_doorbell`]. The host notifies the guest
+/// by setting bits in the pending register using [`SharedMemoryHandle::notify`].
+///
+/// There is no interrupt controller yet, so the guest has to poll the pending register.
+/// Once there is one, it can raise an interrupt while
+/// [`SharedMemoryHandle::interrupt_pending`] returns `true`.
+pub struct SharedMemory {
+    state: Rc<RefCell<SharedState>>,
+    on_doorbell: Option<Box<dyn FnMut(u32)>>,
+}
+
+/// A handle to the buffer of a [`SharedMemory`] device, that can be used after the device
+/// was moved into a bus.
+#[derive(Clone)]
+pub struct SharedMemoryHandle {
+    state: Rc<RefCell<SharedState>>,
+}
+
+struct SharedState {
+    buf: Vec<u8>,
+    pending: u32,
+}
+
+impl SharedMemory {
+    /// The offset of the shared buffer inside the device.
+    pub const BUFFER_OFFSET: u64 = PAGE_SIZE;
+
+    /// Create a new shared memory device with a zeroed buffer of `size` bytes.
+    pub fn new(size: usize) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(SharedState {
+                buf: vec![0; size],
+                pending: 0,
+            })),
+            on_doorbell: None,
+        }
+    }
+
+    /// Call `f` with the written value, whenever the guest writes to the doorbell register.
+    pub fn on_doorbell(mut self, f: impl FnMut(u32) + 'static) -> Self {
+        self.on_doorbell = Some(Box::new(f));
+        self
+    }
+
+    /// Return a handle that can be used to access the shared buffer.
+    pub fn handle(&self) -> SharedMemoryHandle {
+        SharedMemoryHandle {
+            state: Rc::clone(&self.state),
+        }
+    }
+}
+
+impl SharedMemoryHandle {
+    /// Copy the bytes at `off` inside the shared buffer into `buf`.
+    ///
+    /// Returns `false` and leaves `buf` untouched, if the range is outside of the buffer.
+    pub fn read(&self, off: usize, buf: &mut [u8]) -> bool {
+        let state = self.state.borrow();
+        match buffer_range(&state.buf, off, buf.len()) {
+            Some(range) => {
+                buf.copy_from_slice(&state.buf[range]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copy `data` into the shared buffer at `off`.
+    ///
+    /// Returns `false` and leaves the buffer untouched, if the range is outside of it.
+    pub fn write(&self, off: usize, data: &[u8]) -> bool {
+        let mut state = self.state.borrow_mut();
+        match buffer_range(&state.buf, off, data.len()) {
+            Some(range) => {
+                state.buf[range].copy_from_slice(data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the given bits in the pending register, to notify the guest.
+    pub fn notify(&self, bits: u32) {
+        self.state.borrow_mut().pending |= bits;
+    }
+
+    /// Return the bits in the pending register, that were not yet acknowledged by the guest.
+    pub fn pending(&self) -> u32 {
+        self.state.borrow().pending
+    }
+
+    /// Check if the device requests an interrupt, i.e. if any bit in the pending
+    /// register is set.
+    pub fn interrupt_pending(&self) -> bool {
+        self.pending() != 0
+    }
+}
+
+/// Return the range of `len` bytes at `off` inside `buf`, if it is fully contained in it.
+fn buffer_range(buf: &[u8], off: usize, len: usize) -> Option<Range<usize>> {
+    let end = off.checked_add(len)?;
+    if end <= buf.len() {
+        Some(off..end)
+    } else {
+        None
+    }
+}
+
+impl Device for SharedMemory {
+    fn size(&self) -> u64 {
+        Self::BUFFER_OFFSET + self.state.borrow().buf.len() as u64
+    }
+
+    fn kind(&self) -> &'static str {
+        "shmem"
+    }
+
+    fn load(&self, off: u64, buf: &mut [u8]) -> Result<()> {
+        let state = self.state.borrow();
+
+        if let Some(buf_off) = off.checked_sub(Self::BUFFER_OFFSET) {
+            let range = usize::try_from(buf_off)
+                .ok()
+                .and_then(|buf_off| buffer_range(&state.buf, buf_off, buf.len()))
+                .ok_or(Exception::LoadAccessFault(off.into()))?;
+            buf.copy_from_slice(&state.buf[range]);
+            return Ok(());
+        }
+
+        if buf.len() != 4 {
+            return Err(Exception::LoadAccessFault(off.into()));
+        }
+
+        let val = match off {
+            reg::PENDING => state.pending,
+            reg::SIZE => state.buf.len() as u32,
+            _ => 0,
+        };
+        buf.copy_from_slice(&val.to_le_bytes());
+        Ok(())
+    }
+
+    fn write(&mut self, off: u64, buf: &[u8]) -> Result<()> {
+        if let Some(buf_off) = off.checked_sub(Self::BUFFER_OFFSET) {
+            let mut state = self.state.borrow_mut();
+            let range = usize::try_from(buf_off)
+                .ok()
+                .and_then(|buf_off| buffer_range(&state.buf, buf_off, buf.len()))
+                .ok_or(Exception::StoreAccessFault(off.into()))?;
+            state.buf[range].copy_from_slice(buf);
+            return Ok(());
+        }
+
+        let val = match *buf {
+            [a, b, c, d] => u32::from_le_bytes([a, b, c, d]),
+            _ => return Err(Exception::StoreAccessFault(off.into())),
+        };
+
+        match off {
+            reg::DOORBELL => {
+                if let Some(f) = &mut self.on_doorbell {
+                    f(val);
+                }
+            }
+            reg::PENDING => self.state.borrow_mut().pending &= !val,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        // the buffer belongs to the host, so only the notifications are dropped
+        self.state.borrow_mut().pending = 0;
+    }
+}
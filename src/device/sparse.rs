@@ -0,0 +1,105 @@
+use super::{page_chunks, single_page, Device, Exception, Page, Result, PAGE_SIZE};
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+
+/// A [`Device`] which acts as a RAM module, but only allocates the memory for a page
+/// when it is written to for the first time.
+///
+/// Pages that were never written to read as zero. This allows mapping huge amounts of
+/// memory without allocating all of it up front.
+///
+/// Like the [`RamDevice`](super::RamDevice), allocated pages are shared between clones
+/// and copied on write.
+#[derive(Clone)]
+pub struct SparseRamDevice {
+    size: u64,
+    pages: BTreeMap<u64, Arc<Page>>,
+}
+
+impl SparseRamDevice {
+    /// Create a new sparse RAM device that is able to hold `size` bytes of memory.
+    pub fn new(size: u64) -> Self {
+        Self {
+            size,
+            pages: BTreeMap::new(),
+        }
+    }
+
+    /// Return the number of pages that have been allocated so far.
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn in_bounds(&self, off: u64, len: usize) -> bool {
+        matches!(off.checked_add(len as u64), Some(end) if end <= self.size)
+    }
+}
+
+impl Device for SparseRamDevice {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn kind(&self) -> &'static str {
+        "sparse-ram"
+    }
+
+    fn load(&self, off: u64, buf: &mut [u8]) -> Result<()> {
+        if !self.in_bounds(off, buf.len()) {
+            return Err(Exception::LoadAccessFault(off.into()));
+        }
+
+        for (page, page_off, range) in page_chunks(off, buf.len()) {
+            let to = &mut buf[range];
+            match self.pages.get(&page) {
+                Some(page) => to.copy_from_slice(&page[page_off..page_off + to.len()]),
+                None => to.fill(0),
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, off: u64, buf: &[u8]) -> Result<()> {
+        if !self.in_bounds(off, buf.len()) {
+            return Err(Exception::StoreAccessFault(off.into()));
+        }
+
+        for (page, page_off, range) in page_chunks(off, buf.len()) {
+            let from = &buf[range];
+
+            // don't allocate a page just to write zeros into it
+            if !self.pages.contains_key(&page) && from.iter().all(|&x| x == 0) {
+                continue;
+            }
+
+            let page = self
+                .pages
+                .entry(page)
+                .or_insert_with(|| Arc::new([0u8; PAGE_SIZE as usize]));
+            Arc::make_mut(page)[page_off..page_off + from.len()].copy_from_slice(from);
+        }
+        Ok(())
+    }
+
+    fn slice(&self, off: u64, len: usize) -> Option<&[u8]> {
+        static ZERO_PAGE: Page = [0; PAGE_SIZE as usize];
+
+        let (page, range) = single_page(off, len).filter(|_| self.in_bounds(off, len))?;
+        match self.pages.get(&page) {
+            Some(page) => Some(&page[range]),
+            None => Some(&ZERO_PAGE[range]),
+        }
+    }
+
+    fn slice_mut(&mut self, off: u64, len: usize) -> Option<&mut [u8]> {
+        let (page, range) = single_page(off, len).filter(|_| self.in_bounds(off, len))?;
+        let page = self
+            .pages
+            .entry(page)
+            .or_insert_with(|| Arc::new([0u8; PAGE_SIZE as usize]));
+        Some(&mut Arc::make_mut(page)[range])
+    }
+
+    fn fork(&self) -> Option<Box<dyn Device>> {
+        Some(Box::new(self.clone()))
+    }
+}
@@ -0,0 +1,192 @@
+use super::{Device, Exception, Result};
+use alloc::{boxed::Box, collections::VecDeque};
+use core::cell::RefCell;
+
+/// The number of entries in the transmit and receive FIFOs.
+const FIFO_DEPTH: usize = 8;
+
+/// Flag in the `txdata` and `rxdata` registers, that indicates a full or empty FIFO.
+const FIFO_FLAG: u32 = 1 << 31;
+
+/// The `csmode` value that disables the chip select.
+const CSMODE_OFF: u32 = 3;
+
+/// Register offsets of the SPI controller.
+mod reg {
+    pub const SCKDIV: u64 = 0x00;
+    pub const SCKMODE: u64 = 0x04;
+    pub const CSID: u64 = 0x10;
+    pub const CSDEF: u64 = 0x14;
+    pub const CSMODE: u64 = 0x18;
+    pub const FMT: u64 = 0x40;
+    pub const TXDATA: u64 = 0x48;
+    pub const RXDATA: u64 = 0x4C;
+    pub const TXMARK: u64 = 0x50;
+    pub const RXMARK: u64 = 0x54;
+    pub const IE: u64 = 0x70;
+    pub const IP: u64 = 0x74;
+}
+
+/// A device that is connected to a [`SpiController`].
+pub trait SpiDevice {
+    /// Exchange a single byte with the device, returning the byte the device
+    /// shifted out while receiving `mosi`.
+    fn transfer(&mut self, mosi: u8) -> u8;
+
+    /// Called whenever the chip select line of this device changes.
+    fn select(&mut self, selected: bool) {
+        let _ = selected;
+    }
+
+    /// Put the device back into its power-on state. Does nothing by default.
+    fn reset(&mut self) {}
+}
+
+/// A SPI controller with a register layout compatible to the SiFive SPI controller,
+/// that has a single [`SpiDevice`] connected to it.
+///
+/// Transfers complete immediately when a byte is written to `txdata`, so the transmit
+/// FIFO is never full.
+pub struct SpiController {
+    dev: Box<dyn SpiDevice>,
+    rx: RefCell<VecDeque<u8>>,
+    regs: Registers,
+}
+
+/// The control registers of the [`SpiController`].
+struct Registers {
+    sckdiv: u32,
+    sckmode: u32,
+    csid: u32,
+    csdef: u32,
+    csmode: u32,
+    fmt: u32,
+    txmark: u32,
+    rxmark: u32,
+    ie: u32,
+}
+
+impl Default for Registers {
+    /// The reset value of all registers.
+    fn default() -> Self {
+        Self {
+            sckdiv: 3,
+            sckmode: 0,
+            csid: 0,
+            csdef: 1,
+            csmode: 0,
+            // 8 bits per frame, MSB first
+            fmt: 8 << 16,
+            txmark: 0,
+            rxmark: 0,
+            ie: 0,
+        }
+    }
+}
+
+impl SpiController {
+    /// Create a new SPI controller that has `dev` connected to its first chip select.
+    pub fn new(dev: impl SpiDevice + 'static) -> Self {
+        Self {
+            dev: Box::new(dev),
+            rx: RefCell::new(VecDeque::with_capacity(FIFO_DEPTH)),
+            regs: Registers::default(),
+        }
+    }
+
+    fn selected(&self) -> bool {
+        self.regs.csid == 0 && self.regs.csmode != CSMODE_OFF
+    }
+
+    fn ip(&self) -> u32 {
+        let txwm = self.regs.txmark > 0;
+        let rxwm = self.rx.borrow().len() > self.regs.rxmark as usize;
+        (txwm as u32) | ((rxwm as u32) << 1)
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        let miso = if self.selected() {
+            self.dev.transfer(byte)
+        } else {
+            0xFF
+        };
+
+        // the oldest byte is dropped if the receive FIFO overflows
+        let mut rx = self.rx.borrow_mut();
+        if rx.len() == FIFO_DEPTH {
+            rx.pop_front();
+        }
+        rx.push_back(miso);
+    }
+}
+
+impl Device for SpiController {
+    fn size(&self) -> u64 {
+        0x100
+    }
+
+    fn kind(&self) -> &'static str {
+        "spi"
+    }
+
+    fn load(&self, off: u64, buf: &mut [u8]) -> Result<()> {
+        if buf.len() != 4 {
+            return Err(Exception::LoadAccessFault(off.into()));
+        }
+
+        let val = match off {
+            reg::SCKDIV => self.regs.sckdiv,
+            reg::SCKMODE => self.regs.sckmode,
+            reg::CSID => self.regs.csid,
+            reg::CSDEF => self.regs.csdef,
+            reg::CSMODE => self.regs.csmode,
+            reg::FMT => self.regs.fmt,
+            reg::TXDATA => 0,
+            reg::RXDATA => match self.rx.borrow_mut().pop_front() {
+                Some(byte) => byte as u32,
+                None => FIFO_FLAG,
+            },
+            reg::TXMARK => self.regs.txmark,
+            reg::RXMARK => self.regs.rxmark,
+            reg::IE => self.regs.ie,
+            reg::IP => self.ip(),
+            _ => 0,
+        };
+
+        buf.copy_from_slice(&val.to_le_bytes());
+        Ok(())
+    }
+
+    fn write(&mut self, off: u64, buf: &[u8]) -> Result<()> {
+        let val = match *buf {
+            [a, b, c, d] => u32::from_le_bytes([a, b, c, d]),
+            _ => return Err(Exception::StoreAccessFault(off.into())),
+        };
+
+        let was_selected = self.selected();
+        match off {
+            reg::SCKDIV => self.regs.sckdiv = val & 0xFFF,
+            reg::SCKMODE => self.regs.sckmode = val & 0b11,
+            reg::CSID => self.regs.csid = val,
+            reg::CSDEF => self.regs.csdef = val,
+            reg::CSMODE => self.regs.csmode = val & 0b11,
+            reg::FMT => self.regs.fmt = val,
+            reg::TXDATA => self.transmit(val as u8),
+            reg::TXMARK => self.regs.txmark = val & 0b111,
+            reg::RXMARK => self.regs.rxmark = val & 0b111,
+            reg::IE => self.regs.ie = val & 0b11,
+            _ => {}
+        }
+
+        if was_selected != self.selected() {
+            self.dev.select(self.selected());
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.regs = Registers::default();
+        self.rx.get_mut().clear();
+        self.dev.reset();
+    }
+}
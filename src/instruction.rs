@@ -3,10 +3,14 @@
 
 #[macro_use]
 mod macros;
+mod decoded;
 mod fmt;
 
+pub use decoded::DecodedInstruction;
+
 pub mod parse;
-pub use parse::decode;
+pub mod semantics;
+pub use parse::{decode, decode_for, decode_with_nops, likely_extension};
 
 /// Enum for representing the different instruction formats.
 #[derive(Debug)]
@@ -35,6 +39,16 @@ mod from_impls {
     impl From<BType> for InstructionType { fn from(x: BType) -> Self { Self::B(x) } }
     impl From<UType> for InstructionType { fn from(x: UType) -> Self { Self::U(x) } }
     impl From<JType> for InstructionType { fn from(x: JType) -> Self { Self::J(x) } }
+
+    // the ordering bits are encoded in `funct7`
+    impl From<AmoType> for InstructionType {
+        fn from(x: AmoType) -> Self { Self::R(RType { rd: x.rd, rs1: x.rs1, rs2: x.rs2 }) }
+    }
+
+    // the byte select is encoded in the upper bits of `funct7`
+    impl From<AesType> for InstructionType {
+        fn from(x: AesType) -> Self { Self::R(RType { rd: x.rd, rs1: x.rs1, rs2: x.rs2 }) }
+    }
 }
 
 /// Type safe access for a X register.
@@ -63,6 +77,69 @@ impl Register {
     }
 }
 
+impl Register {
+    /// The ABI names of all registers, indexed by the register index.
+    const ABI_NAMES: [&'static str; 32] = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+        "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+        "t5", "t6",
+    ];
+
+    /// Return the raw index of this register.
+    #[inline]
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    /// Return the ABI name of this register, e.g. `"a0"` for `x10`.
+    pub fn abi_name(self) -> &'static str {
+        Self::ABI_NAMES[self.0 as usize]
+    }
+
+    /// Look up a register by its ABI name (e.g. `"a0"`), by `"fp"`, or by its
+    /// architectural name (e.g. `"x10"`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name == "fp" {
+            return Some(Self(8));
+        }
+
+        if let Some(idx) = Self::ABI_NAMES.iter().position(|&abi| abi == name) {
+            return Some(Self(idx as u8));
+        }
+
+        let idx = name.strip_prefix('x')?;
+        // reject things like `x01` or `x+1`, which `parse` would accept
+        if idx.is_empty() || (idx.len() > 1 && idx.starts_with('0')) || idx.starts_with('+') {
+            return None;
+        }
+        match idx.parse() {
+            Ok(idx @ 0..=31) => Some(Self(idx)),
+            _ => None,
+        }
+    }
+}
+
+impl core::str::FromStr for Register {
+    type Err = InvalidRegisterName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or(InvalidRegisterName)
+    }
+}
+
+/// The error that is returned when parsing an unknown register name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidRegisterName;
+
+impl core::fmt::Display for InvalidRegisterName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid register name")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidRegisterName {}
+
 impl From<u8> for Register {
     fn from(x: u8) -> Self {
         Self::new(x)
@@ -80,6 +157,36 @@ pub struct RType {
     pub rs2: Register,
 }
 
+/// The R instruction format used by the AES instructions of the scalar cryptography
+/// extensions, which select a byte of `rs2` using the `bs` field.
+#[derive(Debug, Clone)]
+pub struct AesType {
+    /// The destination register index.
+    pub rd: Register,
+    /// The first source register index.
+    pub rs1: Register,
+    /// The second source register index.
+    pub rs2: Register,
+    /// The byte select field.
+    pub bs: u8,
+}
+
+/// The R instruction format used by atomic memory operations, which contains the
+/// memory ordering bits in `funct7`.
+#[derive(Debug, Clone)]
+pub struct AmoType {
+    /// The destination register index.
+    pub rd: Register,
+    /// The register index that contains the address.
+    pub rs1: Register,
+    /// The source register index.
+    pub rs2: Register,
+    /// Whether this access has acquire semantics.
+    pub aq: bool,
+    /// Whether this access has release semantics.
+    pub rl: bool,
+}
+
 /// The I instruction format.
 #[derive(Debug, Clone)]
 pub struct IType {
@@ -181,6 +288,24 @@ impl JType {
     }
 }
 
+impl Instruction {
+    /// Check if this is an instruction that only has a destination and a single source
+    /// register, and uses the immediate field of its I-type encoding to select the operation.
+    pub(crate) fn is_unary(&self) -> bool {
+        matches!(
+            self,
+            Instruction::BREV8(_)
+                | Instruction::REV8(_)
+                | Instruction::ZIP(_)
+                | Instruction::UNZIP(_)
+                | Instruction::SHA256SIG0(_)
+                | Instruction::SHA256SIG1(_)
+                | Instruction::SHA256SUM0(_)
+                | Instruction::SHA256SUM1(_)
+        )
+    }
+}
+
 instructions! {
     base(RV32I) [
         LUI(UType),
@@ -228,8 +353,63 @@ instructions! {
         AND(RType),
 
         FENCE(IType),
-        FENCEI(IType),
         ECALL(IType),
         EBREAK(IType),
     ]
+
+    ext("Zifencei") [
+        FENCEI(IType),
+    ]
+
+    ext("Zihintpause") [
+        PAUSE(IType),
+    ]
+
+    ext("Zbkb") [
+        ROL(RType),
+        ROR(RType),
+        RORI(IType),
+        ANDN(RType),
+        ORN(RType),
+        XNOR(RType),
+        PACK(RType),
+        PACKH(RType),
+        BREV8(IType),
+        REV8(IType),
+        ZIP(IType),
+        UNZIP(IType),
+    ]
+
+    ext("Zknd") [
+        AES32DSI(AesType),
+        AES32DSMI(AesType),
+    ]
+
+    ext("Zkne") [
+        AES32ESI(AesType),
+        AES32ESMI(AesType),
+    ]
+
+    ext("Zknh") [
+        SHA256SIG0(IType),
+        SHA256SIG1(IType),
+        SHA256SUM0(IType),
+        SHA256SUM1(IType),
+        SHA512SIG0H(RType),
+        SHA512SIG0L(RType),
+        SHA512SIG1H(RType),
+        SHA512SIG1L(RType),
+        SHA512SUM0R(RType),
+        SHA512SUM1R(RType),
+    ]
+
+    ext("Zacas") [
+        AMOCASW(AmoType),
+        AMOCASD(AmoType),
+    ]
+
+    ext("Zawrs") [
+        WRSNTO(IType),
+        WRSSTO(IType),
+    ]
 }
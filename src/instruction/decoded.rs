@@ -0,0 +1,125 @@
+//! A uniform view over decoded instructions.
+
+use super::{decode, Instruction, InstructionType, Register};
+
+/// A decoded instruction together with its operands in a format independent representation.
+///
+/// This is meant for tools that analyze instructions and only care about which registers
+/// and immediate are used, without matching on every [`Instruction`] and [`InstructionType`].
+#[derive(Debug)]
+pub struct DecodedInstruction {
+    inst: Instruction,
+    len: u8,
+}
+
+impl DecodedInstruction {
+    /// Decode the raw instruction into a [`DecodedInstruction`].
+    ///
+    /// Returns `None` if the instruction is not valid.
+    pub fn decode(raw: u32) -> Option<Self> {
+        decode(raw).map(|inst| Self { inst, len: 4 })
+    }
+
+    /// Return the underlying [`Instruction`].
+    #[inline]
+    pub fn instruction(&self) -> &Instruction {
+        &self.inst
+    }
+
+    /// Return the mnemonic of this instruction, e.g. `"addi"`.
+    #[inline]
+    pub fn mnemonic(&self) -> &'static str {
+        self.inst.mnemonic()
+    }
+
+    /// Return the length of the encoded instruction in bytes.
+    #[inline]
+    pub fn length(&self) -> u8 {
+        self.len
+    }
+
+    /// Return the destination register, if this instruction writes one.
+    pub fn rd(&self) -> Option<Register> {
+        match self.operands()? {
+            InstructionType::R(ty) => Some(ty.rd),
+            InstructionType::I(ty) => Some(ty.rd),
+            InstructionType::U(ty) => Some(ty.rd),
+            InstructionType::J(ty) => Some(ty.rd),
+            InstructionType::S(_) | InstructionType::B(_) => None,
+        }
+    }
+
+    /// Return the first source register, if this instruction reads one.
+    pub fn rs1(&self) -> Option<Register> {
+        match self.operands()? {
+            InstructionType::R(ty) => Some(ty.rs1),
+            InstructionType::I(ty) => Some(ty.rs),
+            InstructionType::S(ty) => Some(ty.rs1),
+            InstructionType::B(ty) => Some(ty.rs1),
+            InstructionType::U(_) | InstructionType::J(_) => None,
+        }
+    }
+
+    /// Return the second source register, if this instruction reads one.
+    pub fn rs2(&self) -> Option<Register> {
+        if self.inst.is_unary() {
+            return None;
+        }
+
+        match self.operands()? {
+            InstructionType::R(ty) => Some(ty.rs2),
+            InstructionType::S(ty) => Some(ty.rs2),
+            InstructionType::B(ty) => Some(ty.rs2),
+            InstructionType::I(_) | InstructionType::U(_) | InstructionType::J(_) => None,
+        }
+    }
+
+    /// Return the sign-extended immediate of this instruction, if it has one.
+    ///
+    /// For shifts this is the shift amount, and for `lui` and `auipc` this is the
+    /// already shifted upper immediate. The AES instructions return their byte select.
+    pub fn imm(&self) -> Option<i64> {
+        match &self.inst {
+            Instruction::AES32DSI(ty)
+            | Instruction::AES32DSMI(ty)
+            | Instruction::AES32ESI(ty)
+            | Instruction::AES32ESMI(ty) => return Some(ty.bs as i64),
+            inst if inst.is_unary() => return None,
+            _ => {}
+        }
+
+        let imm = match self.operands()? {
+            InstructionType::R(_) => return None,
+            InstructionType::I(ty) => ty.sign_imm(),
+            InstructionType::S(ty) => ty.sign_imm(),
+            InstructionType::B(ty) => ty.sign_imm(),
+            InstructionType::U(ty) => ty.imm() as i32,
+            InstructionType::J(ty) => ty.sign_imm(),
+        };
+        Some(imm as i64)
+    }
+
+    /// Check if this instruction is a hint, see [`Instruction::is_hint`].
+    #[inline]
+    pub fn is_hint(&self) -> bool {
+        self.inst.is_hint()
+    }
+
+    /// Return the operands of this instruction, or `None` if the
+    /// instruction doesn't take any operands.
+    ///
+    /// The `rd`, `rs1` and immediate fields of fences are reserved or describe the ordering,
+    /// so fences don't have operands either.
+    fn operands(&self) -> Option<InstructionType> {
+        match self.inst {
+            Instruction::FENCE(_)
+            | Instruction::FENCEI(_)
+            | Instruction::PAUSE(_)
+            | Instruction::WRSNTO(_)
+            | Instruction::WRSSTO(_)
+            | Instruction::ECALL(_)
+            | Instruction::EBREAK(_) => None,
+            _ => Some(self.inst.inst_type()),
+        }
+    }
+}
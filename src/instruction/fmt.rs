@@ -5,41 +5,7 @@ use core::fmt;
 
 impl fmt::Display for Register {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.0 {
-            0 => write!(f, "zero"),
-            1 => write!(f, "ra"),
-            2 => write!(f, "sp"),
-            3 => write!(f, "gp"),
-            4 => write!(f, "tp"),
-            5 => write!(f, "t0"),
-            6 => write!(f, "t1"),
-            7 => write!(f, "t2"),
-            8 => write!(f, "s0"),
-            9 => write!(f, "s1"),
-            10 => write!(f, "a0"),
-            11 => write!(f, "a1"),
-            12 => write!(f, "a2"),
-            13 => write!(f, "a3"),
-            14 => write!(f, "a4"),
-            15 => write!(f, "a5"),
-            16 => write!(f, "a6"),
-            17 => write!(f, "a7"),
-            18 => write!(f, "s2"),
-            19 => write!(f, "s3"),
-            20 => write!(f, "s4"),
-            21 => write!(f, "s5"),
-            22 => write!(f, "s6"),
-            23 => write!(f, "s7"),
-            24 => write!(f, "s8"),
-            25 => write!(f, "s9"),
-            26 => write!(f, "s10"),
-            27 => write!(f, "s11"),
-            28 => write!(f, "t3"),
-            29 => write!(f, "t4"),
-            30 => write!(f, "t5"),
-            31 => write!(f, "t6"),
-            _ => unreachable!(),
-        }
+        f.write_str(self.abi_name())
     }
 }
 
@@ -49,6 +15,18 @@ impl fmt::Display for RType {
     }
 }
 
+impl fmt::Display for AmoType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}, ({})", self.rd, self.rs2, self.rs1)
+    }
+}
+
+impl fmt::Display for AesType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}, {}, {}", self.rd, self.rs1, self.rs2, self.bs)
+    }
+}
+
 impl fmt::Display for IType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let imm = self.sign_imm();
@@ -84,52 +62,127 @@ impl fmt::Display for JType {
     }
 }
 
-impl fmt::Display for Instruction {
+impl Instruction {
+    /// Return the assembler mnemonic of this instruction, e.g. `"addi"`.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::LUI(_) => "lui",
+            Instruction::AUIPC(_) => "auipc",
+            Instruction::JAL(_) => "jal",
+            Instruction::JALR(_) => "jalr",
+            Instruction::BEQ(_) => "beq",
+            Instruction::BNE(_) => "bne",
+            Instruction::BLT(_) => "blt",
+            Instruction::BGE(_) => "bge",
+            Instruction::BLTU(_) => "bltu",
+            Instruction::BGEU(_) => "bgeu",
+            Instruction::LB(_) => "lb",
+            Instruction::LH(_) => "lh",
+            Instruction::LW(_) => "lw",
+            Instruction::LBU(_) => "lbu",
+            Instruction::LHU(_) => "lhu",
+            Instruction::SB(_) => "sb",
+            Instruction::SH(_) => "sh",
+            Instruction::SW(_) => "sw",
+            Instruction::ADDI(_) => "addi",
+            Instruction::SLTI(_) => "slti",
+            Instruction::SLTIU(_) => "sltiu",
+            Instruction::XORI(_) => "xori",
+            Instruction::ORI(_) => "ori",
+            Instruction::ANDI(_) => "andi",
+            Instruction::SLLI(_) => "slli",
+            Instruction::SRLI(_) => "srli",
+            Instruction::SRAI(_) => "srai",
+            Instruction::ADD(_) => "add",
+            Instruction::SUB(_) => "sub",
+            Instruction::SLL(_) => "sll",
+            Instruction::SLT(_) => "slt",
+            Instruction::SLTU(_) => "sltu",
+            Instruction::XOR(_) => "xor",
+            Instruction::SRL(_) => "srl",
+            Instruction::SRA(_) => "sra",
+            Instruction::OR(_) => "or",
+            Instruction::AND(_) => "and",
+            Instruction::FENCE(_) => "fence",
+            Instruction::FENCEI(_) => "fencei",
+            Instruction::PAUSE(_) => "pause",
+            Instruction::ECALL(_) => "ecall",
+            Instruction::EBREAK(_) => "ebreak",
+            Instruction::ROL(_) => "rol",
+            Instruction::ROR(_) => "ror",
+            Instruction::RORI(_) => "rori",
+            Instruction::ANDN(_) => "andn",
+            Instruction::ORN(_) => "orn",
+            Instruction::XNOR(_) => "xnor",
+            Instruction::PACK(_) => "pack",
+            Instruction::PACKH(_) => "packh",
+            Instruction::BREV8(_) => "brev8",
+            Instruction::REV8(_) => "rev8",
+            Instruction::ZIP(_) => "zip",
+            Instruction::UNZIP(_) => "unzip",
+            Instruction::AES32DSI(_) => "aes32dsi",
+            Instruction::AES32DSMI(_) => "aes32dsmi",
+            Instruction::AES32ESI(_) => "aes32esi",
+            Instruction::AES32ESMI(_) => "aes32esmi",
+            Instruction::SHA256SIG0(_) => "sha256sig0",
+            Instruction::SHA256SIG1(_) => "sha256sig1",
+            Instruction::SHA256SUM0(_) => "sha256sum0",
+            Instruction::SHA256SUM1(_) => "sha256sum1",
+            Instruction::SHA512SIG0H(_) => "sha512sig0h",
+            Instruction::SHA512SIG0L(_) => "sha512sig0l",
+            Instruction::SHA512SIG1H(_) => "sha512sig1h",
+            Instruction::SHA512SIG1L(_) => "sha512sig1l",
+            Instruction::SHA512SUM0R(_) => "sha512sum0r",
+            Instruction::SHA512SUM1R(_) => "sha512sum1r",
+            Instruction::AMOCASW(_) => "amocas.w",
+            Instruction::AMOCASD(_) => "amocas.d",
+            Instruction::WRSNTO(_) => "wrs.nto",
+            Instruction::WRSSTO(_) => "wrs.sto",
+        }
+    }
+}
+
+impl fmt::Display for InstructionType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Instruction::LUI(ty) => write!(f, "lui {}", ty)?,
-            Instruction::AUIPC(ty) => write!(f, "auipc {}", ty)?,
-            Instruction::JAL(ty) => write!(f, "jal {}", ty)?,
-            Instruction::JALR(ty) => write!(f, "jalr {}", ty)?,
-            Instruction::BEQ(ty) => write!(f, "beq {}", ty)?,
-            Instruction::BNE(ty) => write!(f, "bne {}", ty)?,
-            Instruction::BLT(ty) => write!(f, "blt {}", ty)?,
-            Instruction::BGE(ty) => write!(f, "bge {}", ty)?,
-            Instruction::BLTU(ty) => write!(f, "bltu {}", ty)?,
-            Instruction::BGEU(ty) => write!(f, "bgeu {}", ty)?,
-            Instruction::LB(ty) => write!(f, "lb {}", ty)?,
-            Instruction::LH(ty) => write!(f, "lh {}", ty)?,
-            Instruction::LW(ty) => write!(f, "lw {}", ty)?,
-            Instruction::LBU(ty) => write!(f, "lbu {}", ty)?,
-            Instruction::LHU(ty) => write!(f, "lhu {}", ty)?,
-            Instruction::SB(ty) => write!(f, "sb {}", ty)?,
-            Instruction::SH(ty) => write!(f, "sh {}", ty)?,
-            Instruction::SW(ty) => write!(f, "sw {}", ty)?,
-            Instruction::ADDI(ty) => write!(f, "addi {}", ty)?,
-            Instruction::SLTI(ty) => write!(f, "slti {}", ty)?,
-            Instruction::SLTIU(ty) => write!(f, "sltiu {}", ty)?,
-            Instruction::XORI(ty) => write!(f, "xori {}", ty)?,
-            Instruction::ORI(ty) => write!(f, "ori {}", ty)?,
-            Instruction::ANDI(ty) => write!(f, "andi {}", ty)?,
-            Instruction::SLLI(ty) => write!(f, "slli {}", ty)?,
-            Instruction::SRLI(ty) => write!(f, "srli {}", ty)?,
-            Instruction::SRAI(ty) => write!(f, "srai {}", ty)?,
-            Instruction::ADD(ty) => write!(f, "add {}", ty)?,
-            Instruction::SUB(ty) => write!(f, "sub {}", ty)?,
-            Instruction::SLL(ty) => write!(f, "sll {}", ty)?,
-            Instruction::SLT(ty) => write!(f, "slt {}", ty)?,
-            Instruction::SLTU(ty) => write!(f, "sltu {}", ty)?,
-            Instruction::XOR(ty) => write!(f, "xor {}", ty)?,
-            Instruction::SRL(ty) => write!(f, "srl {}", ty)?,
-            Instruction::SRA(ty) => write!(f, "sra {}", ty)?,
-            Instruction::OR(ty) => write!(f, "or {}", ty)?,
-            Instruction::AND(ty) => write!(f, "and {}", ty)?,
-            Instruction::FENCE(ty) => write!(f, "fence {}", ty)?,
-            Instruction::FENCEI(ty) => write!(f, "fencei {}", ty)?,
-            Instruction::ECALL(_) => write!(f, "ecall")?,
-            Instruction::EBREAK(_) => write!(f, "ebreak")?,
+            InstructionType::R(ty) => ty.fmt(f),
+            InstructionType::I(ty) => ty.fmt(f),
+            InstructionType::S(ty) => ty.fmt(f),
+            InstructionType::B(ty) => ty.fmt(f),
+            InstructionType::U(ty) => ty.fmt(f),
+            InstructionType::J(ty) => ty.fmt(f),
         }
+    }
+}
 
-        Ok(())
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::AMOCASW(ty) | Instruction::AMOCASD(ty) => {
+                let order = match (ty.aq, ty.rl) {
+                    (false, false) => "",
+                    (true, false) => ".aq",
+                    (false, true) => ".rl",
+                    (true, true) => ".aqrl",
+                };
+                write!(f, "{}{} {}", self.mnemonic(), order, ty)
+            }
+            Instruction::PAUSE(_)
+            | Instruction::WRSNTO(_)
+            | Instruction::WRSSTO(_)
+            | Instruction::ECALL(_)
+            | Instruction::EBREAK(_) => {
+                write!(f, "{}", self.mnemonic())
+            }
+            Instruction::AES32DSI(ty)
+            | Instruction::AES32DSMI(ty)
+            | Instruction::AES32ESI(ty)
+            | Instruction::AES32ESMI(ty) => write!(f, "{} {}", self.mnemonic(), ty),
+            inst if inst.is_unary() => match inst.inst_type() {
+                InstructionType::I(ty) => write!(f, "{} {}, {}", self.mnemonic(), ty.rd, ty.rs),
+                _ => unreachable!(),
+            },
+            _ => write!(f, "{} {}", self.mnemonic(), self.inst_type()),
+        }
     }
 }
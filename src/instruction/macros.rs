@@ -3,6 +3,11 @@ macro_rules! instructions {
     base($base:ident) [
         $($base_inst:ident ($base_inst_ty:ident) ),*$(,)?
     ]
+    $(
+    ext($ext:literal) [
+        $($ext_inst:ident ($ext_inst_ty:ident) ),*$(,)?
+    ]
+    )*
     ) => {
         /// The instruction type containing every possible
         /// instruction, from every extension.
@@ -11,6 +16,7 @@ macro_rules! instructions {
         #[allow(missing_docs)]
         pub enum Instruction {
             $($base_inst ($base_inst_ty),)*
+            $($($ext_inst ($ext_inst_ty),)*)*
         }
 
         impl Instruction {
@@ -18,6 +24,16 @@ macro_rules! instructions {
             pub fn inst_type(&self) -> $crate::instruction::InstructionType {
                 match self {
                     $(Instruction::$base_inst(ty) => $crate::instruction::InstructionType::from(ty.clone()),)*
+                    $($(Instruction::$ext_inst(ty) => $crate::instruction::InstructionType::from(ty.clone()),)*)*
+                }
+            }
+
+            /// Return the multi-letter extension this instruction belongs to, e.g. `"Zbkb"`,
+            /// or `None` if it is part of the base ISA.
+            pub fn extension(&self) -> Option<&'static str> {
+                match self {
+                    $(Instruction::$base_inst(_) => None,)*
+                    $($(Instruction::$ext_inst(_) => Some($ext),)*)*
                 }
             }
         }
@@ -1,6 +1,7 @@
 //! Instruction decoding.
 
-use super::{BType, IType, Instruction, JType, RType, SType, UType};
+use super::{AesType, AmoType, BType, IType, Instruction, JType, RType, Register, SType, UType};
+use crate::Architecture;
 
 impl RType {
     /// Parse a R-Type instruction from the raw bytes.
@@ -153,6 +154,38 @@ fn get_r_type(ty: RType, funct3: u8, funct7: u8) -> Option<Instruction> {
         (0b101, 0b0100000) => Instruction::SRA(ty),
         (0b110, 0b0000000) => Instruction::OR(ty),
         (0b111, 0b0000000) => Instruction::AND(ty),
+
+        (0b001, 0b0110000) => Instruction::ROL(ty),
+        (0b101, 0b0110000) => Instruction::ROR(ty),
+        (0b111, 0b0100000) => Instruction::ANDN(ty),
+        (0b110, 0b0100000) => Instruction::ORN(ty),
+        (0b100, 0b0100000) => Instruction::XNOR(ty),
+        (0b100, 0b0000100) => Instruction::PACK(ty),
+        (0b111, 0b0000100) => Instruction::PACKH(ty),
+
+        (0b000, 0b0101110) => Instruction::SHA512SIG0H(ty),
+        (0b000, 0b0101010) => Instruction::SHA512SIG0L(ty),
+        (0b000, 0b0101111) => Instruction::SHA512SIG1H(ty),
+        (0b000, 0b0101011) => Instruction::SHA512SIG1L(ty),
+        (0b000, 0b0101000) => Instruction::SHA512SUM0R(ty),
+        (0b000, 0b0101001) => Instruction::SHA512SUM1R(ty),
+
+        // the upper two bits of `funct7` are the byte select of the AES instructions
+        (0b000, funct7) if funct7 & 0x11 == 0x11 => {
+            let ty = AesType {
+                rd: ty.rd,
+                rs1: ty.rs1,
+                rs2: ty.rs2,
+                bs: funct7 >> 5,
+            };
+            match funct7 & 0x1F {
+                0b10001 => Instruction::AES32ESI(ty),
+                0b10011 => Instruction::AES32ESMI(ty),
+                0b10101 => Instruction::AES32DSI(ty),
+                0b10111 => Instruction::AES32DSMI(ty),
+                _ => return None,
+            }
+        }
         _ => return None,
     };
     Some(inst)
@@ -166,6 +199,10 @@ fn get_i_type(mut ty: IType, opcode: u8, funct3: u8) -> Option<Instruction> {
         (0b000_0011, 0b100) => Instruction::LBU(ty),
         (0b000_0011, 0b101) => Instruction::LHU(ty),
 
+        // `fence w, 0` with `fm`, `rs1` and `rd` set to zero
+        (0b000_1111, 0b000) if ty.val == 0x010 && ty.rs.is_zero() && ty.rd.is_zero() => {
+            Instruction::PAUSE(ty)
+        }
         (0b000_1111, 0b000) => Instruction::FENCE(ty),
         (0b000_1111, 0b001) => Instruction::FENCEI(ty),
 
@@ -175,19 +212,63 @@ fn get_i_type(mut ty: IType, opcode: u8, funct3: u8) -> Option<Instruction> {
         (0b001_0011, 0b100) => Instruction::XORI(ty),
         (0b001_0011, 0b110) => Instruction::ORI(ty),
         (0b001_0011, 0b111) => Instruction::ANDI(ty),
-        (0b001_0011, 0b001) => Instruction::SLLI(ty),
-        (0b001_0011, 0b101) => match ty.val & (1 << 10) == 0 {
-            true => Instruction::SRLI(ty),
-            false => {
+        // shifts use the upper 7 bits of the immediate to select the operation, since the shift
+        // amount only has 5 bits on RV32. the other instructions with these `funct3` are unary
+        // operations selected by the whole immediate
+        (0b001_0011, 0b001) => match (ty.val, ty.val >> 5) {
+            (0x08F, _) => Instruction::ZIP(ty),
+            (0x100, _) => Instruction::SHA256SUM0(ty),
+            (0x101, _) => Instruction::SHA256SUM1(ty),
+            (0x102, _) => Instruction::SHA256SIG0(ty),
+            (0x103, _) => Instruction::SHA256SIG1(ty),
+            (_, 0b0000000) => Instruction::SLLI(ty),
+            _ => return None,
+        },
+        (0b001_0011, 0b101) => match (ty.val, ty.val >> 5) {
+            (0x08F, _) => Instruction::UNZIP(ty),
+            (0x687, _) => Instruction::BREV8(ty),
+            (0x698, _) => Instruction::REV8(ty),
+            (_, 0b0000000) => Instruction::SRLI(ty),
+            (_, 0b0100000) => {
                 ty.val &= !(1 << 10);
                 Instruction::SRAI(ty)
             }
+            (_, 0b0110000) => {
+                ty.val &= 0x1F;
+                Instruction::RORI(ty)
+            }
+            _ => return None,
         },
 
         (0b110_0111, 0b000) => Instruction::JALR(ty),
 
-        (0b111_0011, 0b000) if ty.val == 0 => Instruction::ECALL(ty),
-        (0b111_0011, 0b000) if ty.val == 1 => Instruction::EBREAK(ty),
+        // the system instructions without operands are identified by the whole encoding
+        (0b111_0011, 0b000) if ty.rs.is_zero() && ty.rd.is_zero() => match ty.val {
+            0x000 => Instruction::ECALL(ty),
+            0x001 => Instruction::EBREAK(ty),
+            0x00D => Instruction::WRSNTO(ty),
+            0x01D => Instruction::WRSSTO(ty),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(inst)
+}
+
+fn get_amo_type(ty: RType, funct3: u8, funct7: u8) -> Option<Instruction> {
+    let ty = AmoType {
+        rd: ty.rd,
+        rs1: ty.rs1,
+        rs2: ty.rs2,
+        aq: funct7 & 0b10 != 0,
+        rl: funct7 & 0b01 != 0,
+    };
+
+    // `amocas.q` only exists on RV64, and `amocas.d` operates on register pairs on RV32,
+    // so it requires even registers
+    let inst = match (funct3, funct7 >> 2) {
+        (0b010, 0b00101) => Instruction::AMOCASW(ty),
+        (0b011, 0b00101) if (ty.rd.index() | ty.rs2.index()) & 1 == 0 => Instruction::AMOCASD(ty),
         _ => return None,
     };
     Some(inst)
@@ -234,6 +315,9 @@ fn get_j_type(ty: JType, opcode: u8) -> Option<Instruction> {
 }
 
 /// Top level function for decoding a RV32I instruction.
+///
+/// This accepts the instructions of every supported extension, use [`decode_for`] to only
+/// accept the instructions that a specific CPU supports.
 pub fn decode(inst: u32) -> Option<Instruction> {
     // get the opcode from the first 6 bits
     let opcode = (inst & 0x7F) as u8;
@@ -243,6 +327,11 @@ pub fn decode(inst: u32) -> Option<Instruction> {
             let (funct3, funct7, ty) = RType::parse(inst);
             get_r_type(ty, funct3, funct7)
         }
+        // R-variant for atomic memory operations
+        0b010_1111 => {
+            let (funct3, funct7, ty) = RType::parse(inst);
+            get_amo_type(ty, funct3, funct7)
+        }
         // I-variant
         0b000_0011 | 0b000_1111 | 0b001_0011 | 0b110_0111 | 0b111_0011 => {
             let (funct3, ty) = IType::parse(inst);
@@ -271,3 +360,73 @@ pub fn decode(inst: u32) -> Option<Instruction> {
         _ => None,
     }
 }
+
+/// Decode a RV32I instruction for a CPU that implements `arch`.
+///
+/// Instructions of multi-letter extensions that are not enabled in `arch` are treated
+/// as illegal, like every other unknown encoding. The only exception is `pause`, which is
+/// an ordinary `fence` without the Zihintpause extension.
+pub fn decode_for(inst: u32, arch: &Architecture) -> Option<Instruction> {
+    let inst = decode(inst)?;
+    match inst.extension() {
+        Some(ext) if !arch.has_multi_letter_extension(ext) => match inst {
+            Instruction::PAUSE(ty) => Some(Instruction::FENCE(ty)),
+            _ => None,
+        },
+        _ => Some(inst),
+    }
+}
+
+/// Decode a RV32I instruction for a CPU that implements `arch`, like [`decode_for`], but
+/// treat the illegal instructions for which `is_nop` returns `true` as `nop`.
+///
+/// This allows running programs that use instructions which are not implemented, but whose
+/// effects are not needed, e.g. cache management hints.
+pub fn decode_with_nops(
+    inst: u32,
+    arch: &Architecture,
+    is_nop: impl Fn(u32) -> bool,
+) -> Option<Instruction> {
+    match decode_for(inst, arch) {
+        None if is_nop(inst) => Some(Instruction::ADDI(IType {
+            val: 0,
+            rd: Register::new(0),
+            rs: Register::new(0),
+        })),
+        inst => inst,
+    }
+}
+
+/// Guess the extension an instruction belongs to, by looking at its major opcode.
+///
+/// This is meant to diagnose instructions that failed to [`decode`], so the result only
+/// names the most likely extension, e.g. `"M"` for a multiplication or `"C"` for any
+/// compressed instruction, and is not a guarantee that the encoding is valid.
+pub fn likely_extension(inst: u32) -> &'static str {
+    // every instruction whose lowest two bits are not set is compressed
+    if inst & 0b11 != 0b11 {
+        return "C";
+    }
+
+    let funct3 = (inst >> 12) & 0x7;
+    let funct7 = (inst >> 25) & 0x7F;
+    match (inst >> 2) & 0x1F {
+        0b00000 | 0b00100 | 0b00101 | 0b01000 | 0b01101 | 0b11000 | 0b11001 | 0b11011 => "I",
+        0b00011 if funct3 == 0b001 => "Zifencei",
+        0b00011 => "I",
+        0b01100 | 0b01110 if funct7 == 0b000_0001 => "M",
+        0b01100 => "I",
+        0b00110 | 0b01110 => "RV64I",
+        0b00001 | 0b01001 => "F/D/Q/V",
+        0b10000..=0b10100 => "F/D/Q",
+        0b01011 if funct7 >> 2 == 0b00101 => "Zacas",
+        0b01011 => "A",
+        0b10101 => "V",
+        0b11100 if funct3 == 0 => "privileged",
+        0b11100 if funct3 == 0b100 => "H",
+        0b11100 => "Zicsr",
+        0b00010 | 0b01010 | 0b10110 | 0b11110 => "custom",
+        0b00111 | 0b01111 | 0b10111 | 0b11111 => "long instruction",
+        _ => "reserved",
+    }
+}
@@ -0,0 +1,331 @@
+//! A description of what each instruction does, as plain data.
+//!
+//! This allows tools like symbolic execution engines to interpret instructions
+//! without re-implementing the decoder or matching on every [`Instruction`].
+
+use super::{Instruction, Register};
+
+/// A value that is used as an input of an [`Effect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// The value of a register.
+    Reg(Register),
+    /// A sign-extended immediate.
+    Imm(i64),
+    /// The address of the instruction itself.
+    Pc,
+}
+
+/// An arithmetic or logic operation on two [`Operand`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    /// Wrapping addition.
+    Add,
+    /// Wrapping subtraction.
+    Sub,
+    /// Logical left shift by the lower bits of the right operand.
+    Sll,
+    /// `1` if the left operand is less than the right one as signed integers, `0` otherwise.
+    Slt,
+    /// `1` if the left operand is less than the right one as unsigned integers, `0` otherwise.
+    Sltu,
+    /// Bitwise exclusive or.
+    Xor,
+    /// Logical right shift by the lower bits of the right operand.
+    Srl,
+    /// Arithmetic right shift by the lower bits of the right operand.
+    Sra,
+    /// Bitwise or.
+    Or,
+    /// Bitwise and.
+    And,
+    /// Rotate left by the lower bits of the right operand.
+    Rol,
+    /// Rotate right by the lower bits of the right operand.
+    Ror,
+    /// Bitwise and with the inverted right operand.
+    Andn,
+    /// Bitwise or with the inverted right operand.
+    Orn,
+    /// Bitwise exclusive nor.
+    Xnor,
+    /// The lower halves of the left and the right operand, packed into the lower and upper half.
+    Pack,
+    /// The lowest bytes of the left and the right operand, packed into the lowest two bytes.
+    Packh,
+}
+
+/// The comparison of a conditional branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Equal.
+    Eq,
+    /// Not equal.
+    Ne,
+    /// Signed less than.
+    Lt,
+    /// Signed greater than or equal.
+    Ge,
+    /// Unsigned less than.
+    Ltu,
+    /// Unsigned greater than or equal.
+    Geu,
+}
+
+/// The effect an instruction has on the architectural state.
+///
+/// Every effect implicitly advances the PC to the next instruction, unless it
+/// is a taken jump or branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// `rd = lhs <op> rhs`
+    Alu {
+        /// The operation.
+        op: AluOp,
+        /// The register that receives the result.
+        rd: Register,
+        /// The left operand.
+        lhs: Operand,
+        /// The right operand.
+        rhs: Operand,
+    },
+    /// Load `width` bytes from `base + offset` into `rd`.
+    Load {
+        /// The register that receives the loaded value.
+        rd: Register,
+        /// The register containing the base address.
+        base: Register,
+        /// The offset that is added to the base address.
+        offset: i64,
+        /// The number of bytes that are loaded.
+        width: u8,
+        /// Whether the loaded value is sign- or zero-extended.
+        signed: bool,
+    },
+    /// Store the lower `width` bytes of `src` to `base + offset`.
+    Store {
+        /// The register containing the stored value.
+        src: Register,
+        /// The register containing the base address.
+        base: Register,
+        /// The offset that is added to the base address.
+        offset: i64,
+        /// The number of bytes that are stored.
+        width: u8,
+    },
+    /// Write the address of the next instruction into `rd` and jump to `target + offset`.
+    ///
+    /// The lowest bit of the target address is cleared for `jalr`.
+    Jump {
+        /// The register that receives the return address.
+        rd: Register,
+        /// The base of the jump target.
+        target: Operand,
+        /// The offset that is added to the target.
+        offset: i64,
+    },
+    /// Jump to `pc + offset` if `cond` holds for `rs1` and `rs2`.
+    Branch {
+        /// The condition.
+        cond: Condition,
+        /// The first register to compare.
+        rs1: Register,
+        /// The second register to compare.
+        rs2: Register,
+        /// The offset that is added to the PC if the branch is taken.
+        offset: i64,
+    },
+    /// An operation, whose exact semantics are not described as data, like the
+    /// cryptographic instructions. Only the registers it reads and writes are known.
+    Opaque {
+        /// The register that receives the result.
+        rd: Register,
+        /// The first source register.
+        rs1: Register,
+        /// The second source register, if the instruction reads one.
+        rs2: Option<Register>,
+    },
+    /// Atomically compare the `width` bytes at `base` with `rd`, and replace them with `src`
+    /// if they are equal. `rd` receives the original memory value.
+    ///
+    /// If `width` is twice the XLEN, `rd` and `src` are the even registers of a register pair.
+    CompareAndSwap {
+        /// The register that contains the expected and receives the original value.
+        rd: Register,
+        /// The register that contains the address.
+        base: Register,
+        /// The register containing the new value.
+        src: Register,
+        /// The number of bytes that are accessed.
+        width: u8,
+    },
+    /// Stall until the reservation set is invalidated, or a timeout expires.
+    WaitReservation {
+        /// Whether the timeout is short (`wrs.sto`) or implementation defined (`wrs.nto`).
+        short: bool,
+    },
+    /// A memory or instruction fence.
+    Fence,
+    /// A hint that the hart is spin-waiting, so the host may yield to other threads.
+    Pause,
+    /// A request to the execution environment.
+    Ecall,
+    /// A request to the debugger.
+    Ebreak,
+}
+
+impl Instruction {
+    /// Check if this instruction is one of the standard hint encodings.
+    ///
+    /// Hints have no architectural effect besides advancing the PC, e.g. any computation
+    /// that writes `x0` or a fence with an empty predecessor or successor set, and must
+    /// not raise an illegal instruction exception. The canonical `nop` (`addi x0, x0, 0`)
+    /// is not considered a hint.
+    pub fn is_hint(&self) -> bool {
+        match self {
+            Instruction::PAUSE(_) => true,
+            Instruction::FENCE(ty) => {
+                let (pred, succ) = ((ty.val >> 4) & 0xF, ty.val & 0xF);
+                pred == 0 || succ == 0
+            }
+            Instruction::ADDI(ty) => ty.rd.is_zero() && (!ty.rs.is_zero() || ty.val != 0),
+            _ => match self.semantics() {
+                // only computations of the base ISA are hints
+                Effect::Alu { op, rd, .. } => {
+                    use AluOp::*;
+                    rd.is_zero() && !matches!(op, Rol | Ror | Andn | Orn | Xnor | Pack | Packh)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// Describe the effect of this instruction as data.
+    pub fn semantics(&self) -> Effect {
+        use AluOp::*;
+
+        let alu = |op, rd, lhs, rhs| Effect::Alu { op, rd, lhs, rhs };
+        let reg = Operand::Reg;
+        let imm = |x: i32| Operand::Imm(x as i64);
+        let load = |ty: &super::IType, width, signed| Effect::Load {
+            rd: ty.rd,
+            base: ty.rs,
+            offset: ty.sign_imm() as i64,
+            width,
+            signed,
+        };
+        let store = |ty: &super::SType, width| Effect::Store {
+            src: ty.rs2,
+            base: ty.rs1,
+            offset: ty.sign_imm() as i64,
+            width,
+        };
+        let opaque = |rd, rs1, rs2| Effect::Opaque { rd, rs1, rs2 };
+        let cas = |ty: &super::AmoType, width| Effect::CompareAndSwap {
+            rd: ty.rd,
+            base: ty.rs1,
+            src: ty.rs2,
+            width,
+        };
+        let branch = |ty: &super::BType, cond| Effect::Branch {
+            cond,
+            rs1: ty.rs1,
+            rs2: ty.rs2,
+            offset: ty.sign_imm() as i64,
+        };
+
+        match self {
+            Instruction::LUI(ty) => alu(Add, ty.rd, imm(ty.imm() as i32), imm(0)),
+            Instruction::AUIPC(ty) => alu(Add, ty.rd, Operand::Pc, imm(ty.imm() as i32)),
+
+            Instruction::JAL(ty) => Effect::Jump {
+                rd: ty.rd,
+                target: Operand::Pc,
+                offset: ty.sign_imm() as i64,
+            },
+            Instruction::JALR(ty) => Effect::Jump {
+                rd: ty.rd,
+                target: reg(ty.rs),
+                offset: ty.sign_imm() as i64,
+            },
+
+            Instruction::BEQ(ty) => branch(ty, Condition::Eq),
+            Instruction::BNE(ty) => branch(ty, Condition::Ne),
+            Instruction::BLT(ty) => branch(ty, Condition::Lt),
+            Instruction::BGE(ty) => branch(ty, Condition::Ge),
+            Instruction::BLTU(ty) => branch(ty, Condition::Ltu),
+            Instruction::BGEU(ty) => branch(ty, Condition::Geu),
+
+            Instruction::LB(ty) => load(ty, 1, true),
+            Instruction::LH(ty) => load(ty, 2, true),
+            Instruction::LW(ty) => load(ty, 4, true),
+            Instruction::LBU(ty) => load(ty, 1, false),
+            Instruction::LHU(ty) => load(ty, 2, false),
+
+            Instruction::SB(ty) => store(ty, 1),
+            Instruction::SH(ty) => store(ty, 2),
+            Instruction::SW(ty) => store(ty, 4),
+
+            Instruction::ADDI(ty) => alu(Add, ty.rd, reg(ty.rs), imm(ty.sign_imm())),
+            Instruction::SLTI(ty) => alu(Slt, ty.rd, reg(ty.rs), imm(ty.sign_imm())),
+            Instruction::SLTIU(ty) => alu(Sltu, ty.rd, reg(ty.rs), imm(ty.sign_imm())),
+            Instruction::XORI(ty) => alu(Xor, ty.rd, reg(ty.rs), imm(ty.sign_imm())),
+            Instruction::ORI(ty) => alu(Or, ty.rd, reg(ty.rs), imm(ty.sign_imm())),
+            Instruction::ANDI(ty) => alu(And, ty.rd, reg(ty.rs), imm(ty.sign_imm())),
+            Instruction::SLLI(ty) => alu(Sll, ty.rd, reg(ty.rs), imm(ty.shamt() as i32)),
+            Instruction::SRLI(ty) => alu(Srl, ty.rd, reg(ty.rs), imm(ty.shamt() as i32)),
+            Instruction::SRAI(ty) => alu(Sra, ty.rd, reg(ty.rs), imm(ty.shamt() as i32)),
+
+            Instruction::ADD(ty) => alu(Add, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::SUB(ty) => alu(Sub, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::SLL(ty) => alu(Sll, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::SLT(ty) => alu(Slt, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::SLTU(ty) => alu(Sltu, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::XOR(ty) => alu(Xor, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::SRL(ty) => alu(Srl, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::SRA(ty) => alu(Sra, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::OR(ty) => alu(Or, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::AND(ty) => alu(And, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+
+            Instruction::ROL(ty) => alu(Rol, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::ROR(ty) => alu(Ror, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::RORI(ty) => alu(Ror, ty.rd, reg(ty.rs), imm(ty.shamt() as i32)),
+            Instruction::ANDN(ty) => alu(Andn, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::ORN(ty) => alu(Orn, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::XNOR(ty) => alu(Xnor, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::PACK(ty) => alu(Pack, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+            Instruction::PACKH(ty) => alu(Packh, ty.rd, reg(ty.rs1), reg(ty.rs2)),
+
+            Instruction::BREV8(ty)
+            | Instruction::REV8(ty)
+            | Instruction::ZIP(ty)
+            | Instruction::UNZIP(ty)
+            | Instruction::SHA256SIG0(ty)
+            | Instruction::SHA256SIG1(ty)
+            | Instruction::SHA256SUM0(ty)
+            | Instruction::SHA256SUM1(ty) => opaque(ty.rd, ty.rs, None),
+
+            Instruction::AES32DSI(ty)
+            | Instruction::AES32DSMI(ty)
+            | Instruction::AES32ESI(ty)
+            | Instruction::AES32ESMI(ty) => opaque(ty.rd, ty.rs1, Some(ty.rs2)),
+
+            Instruction::SHA512SIG0H(ty)
+            | Instruction::SHA512SIG0L(ty)
+            | Instruction::SHA512SIG1H(ty)
+            | Instruction::SHA512SIG1L(ty)
+            | Instruction::SHA512SUM0R(ty)
+            | Instruction::SHA512SUM1R(ty) => opaque(ty.rd, ty.rs1, Some(ty.rs2)),
+
+            Instruction::AMOCASW(ty) => cas(ty, 4),
+            Instruction::AMOCASD(ty) => cas(ty, 8),
+            Instruction::WRSNTO(_) => Effect::WaitReservation { short: false },
+            Instruction::WRSSTO(_) => Effect::WaitReservation { short: true },
+
+            Instruction::FENCE(_) | Instruction::FENCEI(_) => Effect::Fence,
+            Instruction::PAUSE(_) => Effect::Pause,
+            Instruction::ECALL(_) => Effect::Ecall,
+            Instruction::EBREAK(_) => Effect::Ebreak,
+        }
+    }
+}
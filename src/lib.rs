@@ -1,7 +1,15 @@
 //! A very good RISC-V emulator.
+//!
+//! The core of spear only depends on `alloc`, so it can be used in `no_std` environments
+//! by disabling the default `std` feature.
+#![no_std]
 #![forbid(unsafe_code)]
 #![deny(rustdoc::broken_intra_doc_links, missing_docs)]
 
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
 pub mod device;
 pub mod instruction;
 pub mod trap;
@@ -9,9 +17,28 @@ pub mod trap;
 mod address;
 pub use address::Address;
 
+mod arch;
+pub use arch::Architecture;
+
 /// Defines the base ISA for an RISC-V CPU.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Base {
     /// RV32I Base Integer Instruction Set.
     RV32I,
 }
+
+impl Base {
+    /// Return the native register width of this base ISA in bits.
+    pub fn xlen(self) -> u32 {
+        match self {
+            Base::RV32I => 32,
+        }
+    }
+
+    /// Return the letter that represents this base ISA in the `misa` CSR.
+    pub fn letter(self) -> char {
+        match self {
+            Base::RV32I => 'I',
+        }
+    }
+}
@@ -5,7 +5,47 @@
 use crate::Address;
 
 /// The result type used for everything that can throw a trap.
-pub type Result<T> = std::result::Result<T, Exception>;
+pub type Result<T> = core::result::Result<T, Exception>;
+
+/// The privilege modes a hart can run in.
+///
+/// Modes are ordered by their privilege, so `User < Supervisor < Machine`, and a mode
+/// is allowed to do everything that a lower mode is allowed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PrivilegeMode {
+    /// User mode.
+    User = 0,
+    /// Supervisor mode.
+    Supervisor = 1,
+    /// Machine mode.
+    Machine = 3,
+}
+
+impl PrivilegeMode {
+    /// Check if this mode is at least as privileged as `other`.
+    ///
+    /// This is the check to use for CSR accesses, e.g. `mode.is_at_least(required)`.
+    #[inline]
+    pub fn is_at_least(self, other: PrivilegeMode) -> bool {
+        self >= other
+    }
+
+    /// Return the encoding of this mode, as used in the `xPP` fields of `mstatus`.
+    #[inline]
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Create a mode from its encoding, returning `None` for the reserved value `2`.
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0 => Some(PrivilegeMode::User),
+            1 => Some(PrivilegeMode::Supervisor),
+            3 => Some(PrivilegeMode::Machine),
+            _ => None,
+        }
+    }
+}
 
 /// All the interrupt kinds.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,13 +85,13 @@ impl Interrupt {
 #[allow(missing_docs)]
 pub enum Exception {
     InstructionAddressMisaligned(Address),
-    InstructionAccessFault,
+    InstructionAccessFault(Address),
     IllegalInstruction(u64),
     Breakpoint,
     LoadAddressMisaligned(Address),
     StoreAddressMisaligned(Address),
-    LoadAccessFault,
-    StoreAccessFault,
+    LoadAccessFault(Address),
+    StoreAccessFault(Address),
     /// An environment call taken from U-mode.
     UserEcall,
     /// An environment call taken from S-mode.
@@ -66,16 +106,45 @@ pub enum Exception {
 }
 
 impl Exception {
-    fn cause(self) -> u32 {
+    /// Get the exception that is raised by an `ECALL` instruction executed in the given mode.
+    pub fn ecall(mode: PrivilegeMode) -> Self {
+        match mode {
+            PrivilegeMode::User => Exception::UserEcall,
+            PrivilegeMode::Supervisor => Exception::SupervisorEcall,
+            PrivilegeMode::Machine => Exception::MachineEcall,
+        }
+    }
+
+    /// Replace the address of an access fault with `addr`.
+    ///
+    /// Devices only know the offset of an access inside of them, so the bus uses this to
+    /// report the full address of the access instead.
+    pub(crate) fn at(self, addr: Address) -> Self {
+        match self {
+            Exception::InstructionAccessFault(_) => Exception::InstructionAccessFault(addr),
+            Exception::LoadAccessFault(_) => Exception::LoadAccessFault(addr),
+            Exception::StoreAccessFault(_) => Exception::StoreAccessFault(addr),
+            other => other,
+        }
+    }
+
+    /// Check if this is an asynchronous interrupt, instead of a synchronous exception.
+    #[inline]
+    pub fn is_interrupt(self) -> bool {
+        matches!(self, Exception::Interrupt(_))
+    }
+
+    /// Return the exception code of this trap, without the interrupt bit.
+    pub fn cause(self) -> u32 {
         match self {
             Exception::InstructionAddressMisaligned(..) => 0,
-            Exception::InstructionAccessFault => 1,
+            Exception::InstructionAccessFault(..) => 1,
             Exception::IllegalInstruction(..) => 2,
             Exception::Breakpoint => 3,
             Exception::LoadAddressMisaligned(..) => 4,
-            Exception::LoadAccessFault => 5,
+            Exception::LoadAccessFault(..) => 5,
             Exception::StoreAddressMisaligned(..) => 6,
-            Exception::StoreAccessFault => 7,
+            Exception::StoreAccessFault(..) => 7,
             Exception::UserEcall => 8,
             Exception::SupervisorEcall => 9,
             Exception::MachineEcall => 11,
@@ -86,14 +155,16 @@ impl Exception {
         }
     }
 
-    fn trap_value(&self, pc: Address) -> Address {
+    /// Return the value that is written to `xtval` if this exception is raised by the
+    /// instruction at `pc`.
+    pub fn trap_value(&self, pc: Address) -> Address {
         match self {
-            Exception::InstructionAccessFault
-            | Exception::Breakpoint
-            | Exception::LoadAccessFault
-            | Exception::StoreAccessFault => pc,
+            Exception::Breakpoint => pc,
             Exception::InstructionPageFault(val)
             | Exception::InstructionAddressMisaligned(val)
+            | Exception::InstructionAccessFault(val)
+            | Exception::LoadAccessFault(val)
+            | Exception::StoreAccessFault(val)
             | Exception::LoadAddressMisaligned(val)
             | Exception::StoreAddressMisaligned(val)
             | Exception::LoadPageFault(val)
@@ -103,3 +174,94 @@ impl Exception {
         }
     }
 }
+
+/// Everything that is needed to take a trap, captured at the time the trap is raised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapContext {
+    /// The exception or interrupt that caused the trap.
+    pub exception: Exception,
+    /// The value that is written to `xtval`.
+    pub tval: Address,
+    /// The address of the instruction that caused the trap, or that was interrupted.
+    pub pc: Address,
+    /// The privilege mode the hart was running in when the trap was raised.
+    pub mode: PrivilegeMode,
+}
+
+impl TrapContext {
+    /// Create the context for `exception`, raised by the instruction at `pc` in `mode`.
+    pub fn new(exception: Exception, pc: Address, mode: PrivilegeMode) -> Self {
+        Self {
+            exception,
+            tval: exception.trap_value(pc),
+            pc,
+            mode,
+        }
+    }
+
+    /// Return the value that is written to `xcause` for a hart with the given XLEN,
+    /// which includes the interrupt bit.
+    pub fn cause(&self, xlen: u32) -> u64 {
+        let code = self.exception.cause() as u64;
+        if self.exception.is_interrupt() {
+            code | (1 << (xlen - 1))
+        } else {
+            code
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ecall_causes() {
+        let causes = [
+            (PrivilegeMode::User, Exception::UserEcall, 8),
+            (PrivilegeMode::Supervisor, Exception::SupervisorEcall, 9),
+            (PrivilegeMode::Machine, Exception::MachineEcall, 11),
+        ];
+
+        for (mode, exception, cause) in causes {
+            assert_eq!(Exception::ecall(mode), exception);
+            assert_eq!(exception.cause(), cause);
+            assert_eq!(exception.trap_value(0x1000u32.into()), Address::zero());
+        }
+    }
+
+    #[test]
+    fn trap_context() {
+        let pc = Address::from(0x8000_0000u32);
+        let mode = PrivilegeMode::User;
+
+        let ctx = TrapContext::new(Exception::LoadPageFault(0x1234u32.into()), pc, mode);
+        assert_eq!((ctx.cause(32), ctx.tval), (13, 0x1234u32.into()));
+
+        let ctx = TrapContext::new(Exception::Breakpoint, pc, mode);
+        assert_eq!(ctx.tval, pc);
+
+        let ctx = TrapContext::new(Exception::StoreAccessFault(0x10u32.into()), pc, mode);
+        assert_eq!((ctx.cause(64), ctx.tval), (7, 0x10u32.into()));
+
+        let timer = Exception::Interrupt(Interrupt::MachineTimerInterrupt);
+        let ctx = TrapContext::new(timer, pc, mode);
+        assert_eq!(ctx.cause(32), 0x8000_0007);
+        assert_eq!(ctx.cause(64), 0x8000_0000_0000_0007);
+    }
+
+    #[test]
+    fn privilege_ordering() {
+        use PrivilegeMode::*;
+
+        assert!(User < Supervisor && Supervisor < Machine);
+        assert!(Machine.is_at_least(Supervisor));
+        assert!(Supervisor.is_at_least(Supervisor));
+        assert!(!User.is_at_least(Supervisor));
+
+        for mode in [User, Supervisor, Machine] {
+            assert_eq!(PrivilegeMode::from_bits(mode.bits()), Some(mode));
+        }
+        assert_eq!(PrivilegeMode::from_bits(2), None);
+    }
+}
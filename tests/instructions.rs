@@ -72,7 +72,7 @@ test_instructions! {
         0x00499593: "slli a1, s3, 4",
     }
     test_srai_inst {
-        0x4386D793: "srai a5, a3, 56",
+        0x4186D793: "srai a5, a3, 24",
     }
     test_add_inst {
         0x008506B3: "add a3, a0, s0",
@@ -86,4 +86,278 @@ test_instructions! {
     test_ebreak_inst {
         0x00100073: "ebreak",
     }
+    test_zbkb_inst {
+        0x60C59533: "rol a0, a1, a2",
+        0x60C5D533: "ror a0, a1, a2",
+        0x6075D513: "rori a0, a1, 7",
+        0x40C5F533: "andn a0, a1, a2",
+        0x40C5E533: "orn a0, a1, a2",
+        0x40C5C533: "xnor a0, a1, a2",
+        0x08C5C533: "pack a0, a1, a2",
+        0x08C5F533: "packh a0, a1, a2",
+        0x6875D513: "brev8 a0, a1",
+        0x6985D513: "rev8 a0, a1",
+        0x08F59513: "zip a0, a1",
+        0x08F5D513: "unzip a0, a1",
+    }
+    test_zkn_inst {
+        0xE2C58533: "aes32esi a0, a1, a2, 3",
+        0x26C58533: "aes32esmi a0, a1, a2, 0",
+        0x6AC58533: "aes32dsi a0, a1, a2, 1",
+        0xAEC58533: "aes32dsmi a0, a1, a2, 2",
+        0x10259513: "sha256sig0 a0, a1",
+        0x10359513: "sha256sig1 a0, a1",
+        0x10059513: "sha256sum0 a0, a1",
+        0x10159513: "sha256sum1 a0, a1",
+        0x5CC58533: "sha512sig0h a0, a1, a2",
+        0x54C58533: "sha512sig0l a0, a1, a2",
+        0x5EC58533: "sha512sig1h a0, a1, a2",
+        0x56C58533: "sha512sig1l a0, a1, a2",
+        0x50C58533: "sha512sum0r a0, a1, a2",
+        0x52C58533: "sha512sum1r a0, a1, a2",
+    }
+    test_zacas_inst {
+        0x28C5A52F: "amocas.w a0, a2, (a1)",
+        0x2EC5B52F: "amocas.d.aqrl a0, a2, (a1)",
+    }
+    test_zawrs_inst {
+        0x00D00073: "wrs.nto",
+        0x01D00073: "wrs.sto",
+    }
+}
+
+#[test]
+fn test_likely_extension() {
+    use spear::instruction::{decode, likely_extension};
+
+    let insts = [
+        (0x008506B3, "I"),
+        (0x02B50533, "M"),
+        (0x0000100F, "Zifencei"),
+        (0x100527AF, "A"),
+        (0x28C5A52F, "Zacas"),
+        (0x00052007, "F/D/Q/V"),
+        (0x30200073, "privileged"),
+        (0x30529073, "Zicsr"),
+        (0x6805C573, "H"),
+        (0x0000000B, "custom"),
+        (0x00004501, "C"),
+    ];
+
+    for (raw, ext) in insts {
+        assert_eq!(likely_extension(raw), ext, "{:#010x}", raw);
+    }
+    assert!(decode(0x02B50533).is_none());
+}
+
+#[test]
+fn test_decoded_instruction() {
+    use spear::instruction::{DecodedInstruction, Register};
+
+    let inst = DecodedInstruction::decode(0x008506B3).unwrap();
+    assert_eq!(inst.mnemonic(), "add");
+    assert_eq!(inst.rd(), Some(Register::new(13)));
+    assert_eq!(inst.rs1(), Some(Register::new(10)));
+    assert_eq!(inst.rs2(), Some(Register::new(8)));
+    assert_eq!(inst.imm(), None);
+    assert_eq!(inst.length(), 4);
+
+    let inst = DecodedInstruction::decode(0xFF0784E3).unwrap();
+    assert_eq!(inst.mnemonic(), "beq");
+    assert_eq!(inst.rd(), None);
+    assert_eq!(inst.imm(), Some(-24));
+
+    let inst = DecodedInstruction::decode(0x05555637).unwrap();
+    assert_eq!(inst.mnemonic(), "lui");
+    assert_eq!(inst.rs1(), None);
+    assert_eq!(inst.imm(), Some(0x0555_5000));
+
+    let inst = DecodedInstruction::decode(0x00000073).unwrap();
+    assert_eq!(inst.mnemonic(), "ecall");
+    assert_eq!((inst.rd(), inst.rs1(), inst.imm()), (None, None, None));
+
+    for raw in [0x0FF0000F, 0x0000100F, 0x0100000F] {
+        let inst = DecodedInstruction::decode(raw).unwrap();
+        let operands = (inst.rd(), inst.rs1(), inst.rs2(), inst.imm());
+        assert_eq!(operands, (None, None, None, None), "{}", inst.mnemonic());
+    }
+
+    assert!(DecodedInstruction::decode(0x02B50533).is_none());
+}
+
+#[test]
+fn test_register_names() {
+    use spear::instruction::Register;
+
+    for idx in 0..32 {
+        let reg = Register::new(idx);
+        assert_eq!(reg.to_string().parse(), Ok(reg));
+        assert_eq!(format!("x{}", idx).parse(), Ok(reg));
+    }
+
+    assert_eq!(Register::from_name("a0"), Some(Register::new(10)));
+    assert_eq!(Register::from_name("fp"), Some(Register::new(8)));
+    assert_eq!(Register::new(8).abi_name(), "s0");
+    for name in ["x32", "x01", "x+1", "x", "a8", ""] {
+        assert_eq!(Register::from_name(name), None, "{:?}", name);
+    }
+}
+
+#[test]
+fn test_semantics() {
+    use spear::instruction::{
+        decode,
+        semantics::{AluOp, Condition, Effect, Operand},
+        Register,
+    };
+
+    let effect = |raw| decode(raw).unwrap().semantics();
+    let reg = Register::new;
+
+    assert_eq!(
+        effect(0x008506B3),
+        Effect::Alu {
+            op: AluOp::Add,
+            rd: reg(13),
+            lhs: Operand::Reg(reg(10)),
+            rhs: Operand::Reg(reg(8)),
+        }
+    );
+    assert_eq!(
+        effect(0x4186D793),
+        Effect::Alu {
+            op: AluOp::Sra,
+            rd: reg(15),
+            lhs: Operand::Reg(reg(13)),
+            rhs: Operand::Imm(24),
+        }
+    );
+    assert_eq!(
+        effect(0x00017517),
+        Effect::Alu {
+            op: AluOp::Add,
+            rd: reg(10),
+            lhs: Operand::Pc,
+            rhs: Operand::Imm(23 << 12),
+        }
+    );
+    assert_eq!(
+        effect(0xFF0784E3),
+        Effect::Branch {
+            cond: Condition::Eq,
+            rs1: reg(15),
+            rs2: reg(16),
+            offset: -24,
+        }
+    );
+    assert_eq!(
+        effect(0xA78080E7),
+        Effect::Jump {
+            rd: reg(1),
+            target: Operand::Reg(reg(1)),
+            offset: -1416,
+        }
+    );
+    assert_eq!(effect(0x00000073), Effect::Ecall);
+}
+
+#[test]
+fn test_hints() {
+    use spear::instruction::{decode, semantics::Effect, DecodedInstruction};
+
+    let pause = DecodedInstruction::decode(0x0100000F).unwrap();
+    assert_eq!(pause.instruction().to_string(), "pause");
+    assert_eq!(pause.instruction().semantics(), Effect::Pause);
+    assert!(pause.is_hint());
+
+    // nop, li x0, 1, slti x0, a0, 5, lui x0, 1, add x0, a0, a1, fence 0, rw
+    assert!(!decode(0x00000013).unwrap().is_hint());
+    for raw in [0x00100013, 0x00552013, 0x00001037, 0x00B50033, 0x0030000F] {
+        let inst = decode(raw).unwrap();
+        assert!(inst.is_hint(), "{}", inst);
+    }
+
+    // fence rw, rw and addi a0, a0, 1 are no hints
+    assert!(!decode(0x0330000F).unwrap().is_hint());
+    assert!(!decode(0x00150513).unwrap().is_hint());
+}
+
+#[test]
+fn test_crypto_operands() {
+    use spear::instruction::{decode, DecodedInstruction, Register};
+
+    let aes = DecodedInstruction::decode(0xE2C58533).unwrap();
+    assert_eq!(aes.rs2(), Some(Register::new(12)));
+    assert_eq!(aes.imm(), Some(3));
+
+    let sha = DecodedInstruction::decode(0x10259513).unwrap();
+    assert_eq!(sha.rs1(), Some(Register::new(11)));
+    assert_eq!((sha.rs2(), sha.imm()), (None, None));
+
+    // shifts with unknown upper immediate bits are not valid
+    assert!(decode(0x04359513).is_none());
+    // and neither are shift amounts above 31 on RV32
+    for raw in [0x03859593, 0x0386D793, 0x4386D793, 0x6235D513] {
+        assert!(decode(raw).is_none(), "{:#010x}", raw);
+    }
+}
+
+#[test]
+fn test_zacas_operands() {
+    use spear::instruction::decode;
+
+    // amocas.q only exists on RV64
+    assert!(decode(0x2CE5C52F).is_none());
+
+    // amocas.d needs even registers for the register pairs
+    assert!(decode(0x2EC5B52F).is_some());
+    assert!(decode(0x2EC5B5AF).is_none());
+    assert!(decode(0x2ED5B52F).is_none());
+}
+
+#[test]
+fn test_decode_for() {
+    use spear::{instruction::decode_for, Architecture, Base};
+
+    let insts = [
+        (0x0000100F, "Zifencei"),
+        (0x60C59533, "Zbkb"),
+        (0x6AC58533, "Zknd"),
+        (0xE2C58533, "Zkne"),
+        (0x10259513, "Zknh"),
+        (0x28C5A52F, "Zacas"),
+        (0x00D00073, "Zawrs"),
+    ];
+
+    // a plain RV32I hart only knows the base instructions
+    let rv32i = Architecture::new(Base::RV32I);
+    assert!(decode_for(0x008506B3, &rv32i).is_some());
+    for (raw, ext) in insts {
+        assert!(decode_for(raw, &rv32i).is_none(), "{:#010x}", raw);
+
+        let arch = rv32i.with_multi_letter_extension(ext);
+        let inst = decode_for(raw, &arch).unwrap();
+        assert_eq!(inst.extension(), Some(ext));
+    }
+
+    // `pause` is a normal fence without Zihintpause
+    let pause = 0x0100000F;
+    assert_eq!(decode_for(pause, &rv32i).unwrap().mnemonic(), "fence");
+    let arch = rv32i.with_multi_letter_extension("Zihintpause");
+    assert_eq!(decode_for(pause, &arch).unwrap().mnemonic(), "pause");
+}
+
+#[test]
+fn test_decode_with_nops() {
+    use spear::{instruction::decode_with_nops, Architecture, Base};
+
+    // treat `cbo.clean` as a nop, but not other unknown encodings
+    let arch = Architecture::new(Base::RV32I);
+    let cbo = |inst: u32| inst & 0xFFF07FFF == 0x0010200F;
+
+    let inst = decode_with_nops(0x0015200F, &arch, cbo).unwrap();
+    assert_eq!(inst.to_string(), "addi zero, zero, 0");
+    assert!(decode_with_nops(0x02B50533, &arch, cbo).is_none());
+    let inst = decode_with_nops(0x008506B3, &arch, cbo).unwrap();
+    assert_eq!(inst.mnemonic(), "add");
 }
@@ -0,0 +1,66 @@
+//! Compares the time it takes to load a large ELF file, against copying every segment into
+//! a temporary buffer first, like the loader did before it wrote segments straight from the file.
+//!
+//! This only prints the timings, run it using
+//! `cargo test --release --test load_timing -- --ignored --nocapture`.
+
+use spear::device::{Device, DeviceBus, RamDevice, DRAM_BASE};
+use std::time::{Duration, Instant};
+
+const FILE_SIZE: u32 = 8 << 20;
+const MEM_SIZE: u32 = 32 << 20;
+const RUNS: u32 = 10;
+
+/// Build an ELF32 executable with a single segment at [`DRAM_BASE`], that contains
+/// `FILE_SIZE` bytes of data and is zero filled up to `MEM_SIZE` bytes.
+fn large_elf() -> Vec<u8> {
+    let words = |elf: &mut Vec<u8>, words: &[u32]| {
+        words
+            .iter()
+            .for_each(|w| elf.extend_from_slice(&w.to_le_bytes()))
+    };
+
+    let mut elf = vec![0x7F, b'E', b'L', b'F', 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    elf.extend_from_slice(&[2, 0, 243, 0]);
+    words(&mut elf, &[1, DRAM_BASE as u32, 52, 0, 0]);
+    elf.extend_from_slice(&[52, 0, 32, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+
+    let base = DRAM_BASE as u32;
+    words(
+        &mut elf,
+        &[1, 0x1000, base, base, FILE_SIZE, MEM_SIZE, 0b111, 0x1000],
+    );
+    elf.resize(0x1000, 0);
+    elf.extend((0..FILE_SIZE).map(|i| i as u8));
+    elf
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        f();
+    }
+    start.elapsed() / RUNS
+}
+
+#[test]
+#[ignore]
+fn load_large_elf() {
+    let elf = large_elf();
+    let data = &elf[0x1000..];
+
+    let direct = time(|| {
+        let mut mem = DeviceBus::new();
+        mem.remove_device(DRAM_BASE.into());
+        mem.load_object(&elf).unwrap();
+    });
+
+    let copied = time(|| {
+        let mut ram = RamDevice::new(MEM_SIZE as usize);
+        let mut segment = vec![0; MEM_SIZE as usize];
+        segment[..data.len()].copy_from_slice(data);
+        ram.write(0, &segment).unwrap();
+    });
+
+    println!("direct: {:?}, copied: {:?}", direct, copied);
+}
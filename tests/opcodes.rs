@@ -0,0 +1,156 @@
+//! Decoder conformance tests using the encodings from the riscv-opcodes project.
+//!
+//! Every instruction is described by the bits that must match, and a mask of the
+//! bits that are fixed by the encoding, exactly like the `MATCH_*` and `MASK_*`
+//! constants generated by riscv-opcodes. Only the RV32 encodings are listed, so the
+//! immediate shifts use the `rv32_i` and `rv32_zbkb` masks with 5-bit shift amounts.
+
+use spear::instruction::decode;
+
+#[rustfmt::skip]
+const OPCODES: &[(&str, u32, u32)] = &[
+    // RV32I
+    ("lui", 0x00000037, 0x0000007f),
+    ("auipc", 0x00000017, 0x0000007f),
+    ("jal", 0x0000006f, 0x0000007f),
+    ("jalr", 0x00000067, 0x0000707f),
+    ("beq", 0x00000063, 0x0000707f),
+    ("bne", 0x00001063, 0x0000707f),
+    ("blt", 0x00004063, 0x0000707f),
+    ("bge", 0x00005063, 0x0000707f),
+    ("bltu", 0x00006063, 0x0000707f),
+    ("bgeu", 0x00007063, 0x0000707f),
+    ("lb", 0x00000003, 0x0000707f),
+    ("lh", 0x00001003, 0x0000707f),
+    ("lw", 0x00002003, 0x0000707f),
+    ("lbu", 0x00004003, 0x0000707f),
+    ("lhu", 0x00005003, 0x0000707f),
+    ("sb", 0x00000023, 0x0000707f),
+    ("sh", 0x00001023, 0x0000707f),
+    ("sw", 0x00002023, 0x0000707f),
+    ("addi", 0x00000013, 0x0000707f),
+    ("slti", 0x00002013, 0x0000707f),
+    ("sltiu", 0x00003013, 0x0000707f),
+    ("xori", 0x00004013, 0x0000707f),
+    ("ori", 0x00006013, 0x0000707f),
+    ("andi", 0x00007013, 0x0000707f),
+    ("slli", 0x00001013, 0xfe00707f),
+    ("srli", 0x00005013, 0xfe00707f),
+    ("srai", 0x40005013, 0xfe00707f),
+    ("add", 0x00000033, 0xfe00707f),
+    ("sub", 0x40000033, 0xfe00707f),
+    ("sll", 0x00001033, 0xfe00707f),
+    ("slt", 0x00002033, 0xfe00707f),
+    ("sltu", 0x00003033, 0xfe00707f),
+    ("xor", 0x00004033, 0xfe00707f),
+    ("srl", 0x00005033, 0xfe00707f),
+    ("sra", 0x40005033, 0xfe00707f),
+    ("or", 0x00006033, 0xfe00707f),
+    ("and", 0x00007033, 0xfe00707f),
+    ("fence", 0x0000000f, 0x0000707f),
+    ("ecall", 0x00000073, 0xffffffff),
+    ("ebreak", 0x00100073, 0xffffffff),
+    // Zifencei
+    ("fencei", 0x0000100f, 0x0000707f),
+    // Zihintpause
+    ("pause", 0x0100000f, 0xffffffff),
+    // Zbkb
+    ("rol", 0x60001033, 0xfe00707f),
+    ("ror", 0x60005033, 0xfe00707f),
+    ("rori", 0x60005013, 0xfe00707f),
+    ("andn", 0x40007033, 0xfe00707f),
+    ("orn", 0x40006033, 0xfe00707f),
+    ("xnor", 0x40004033, 0xfe00707f),
+    ("pack", 0x08004033, 0xfe00707f),
+    ("packh", 0x08007033, 0xfe00707f),
+    ("brev8", 0x68705013, 0xfff0707f),
+    ("rev8", 0x69805013, 0xfff0707f),
+    ("zip", 0x08f01013, 0xfff0707f),
+    ("unzip", 0x08f05013, 0xfff0707f),
+    // Zknd and Zkne
+    ("aes32dsi", 0x2a000033, 0x3e00707f),
+    ("aes32dsmi", 0x2e000033, 0x3e00707f),
+    ("aes32esi", 0x22000033, 0x3e00707f),
+    ("aes32esmi", 0x26000033, 0x3e00707f),
+    // Zknh
+    ("sha256sig0", 0x10201013, 0xfff0707f),
+    ("sha256sig1", 0x10301013, 0xfff0707f),
+    ("sha256sum0", 0x10001013, 0xfff0707f),
+    ("sha256sum1", 0x10101013, 0xfff0707f),
+    ("sha512sig0h", 0x5c000033, 0xfe00707f),
+    ("sha512sig0l", 0x54000033, 0xfe00707f),
+    ("sha512sig1h", 0x5e000033, 0xfe00707f),
+    ("sha512sig1l", 0x56000033, 0xfe00707f),
+    ("sha512sum0r", 0x50000033, 0xfe00707f),
+    ("sha512sum1r", 0x52000033, 0xfe00707f),
+    // Zacas
+    ("amocas.w", 0x2800202f, 0xf800707f),
+    ("amocas.d", 0x2800302f, 0xf800707f),
+    // Zawrs
+    ("wrs.nto", 0x00d00073, 0xffffffff),
+    ("wrs.sto", 0x01d00073, 0xffffffff),
+];
+
+/// A small xorshift generator, so the tests are reproducible.
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// Return the mnemonic of the most specific encoding that matches `inst`.
+fn expected(inst: u32) -> Option<&'static str> {
+    let name = OPCODES
+        .iter()
+        .filter(|(_, bits, mask)| inst & mask == *bits)
+        .max_by_key(|(_, _, mask)| mask.count_ones())
+        .map(|(name, _, _)| *name)?;
+
+    // `amocas.d` uses register pairs on RV32, which must start at an even register
+    let (rd, rs2) = ((inst >> 7) & 0x1F, (inst >> 20) & 0x1F);
+    if name == "amocas.d" && (rd | rs2) & 1 != 0 {
+        return None;
+    }
+    Some(name)
+}
+
+fn mnemonic(inst: u32) -> Option<&'static str> {
+    decode(inst).map(|inst| inst.mnemonic())
+}
+
+#[test]
+fn defined_encodings_decode() {
+    let mut rng = Rng(0x1234_5678);
+
+    for &(name, bits, mask) in OPCODES {
+        assert_eq!(mnemonic(bits), Some(name), "{:#010x}", bits);
+
+        // fill the operand bits with random values
+        for _ in 0..1000 {
+            let inst = (rng.next() & !mask) | bits;
+            assert_eq!(mnemonic(inst), expected(inst), "{:#010x}", inst);
+        }
+    }
+}
+
+#[test]
+fn undefined_encodings_are_rejected() {
+    let mut rng = Rng(0x8765_4321);
+    let opcodes = OPCODES
+        .iter()
+        .map(|(_, bits, _)| bits & 0x7F)
+        .collect::<Vec<_>>();
+
+    // only look at the major opcodes that contain any instructions, since
+    // random words would be rejected by the opcode alone most of the time
+    for _ in 0..1 << 20 {
+        let opcode = opcodes[rng.next() as usize % opcodes.len()];
+        let inst = (rng.next() & !0x7F) | opcode;
+        assert_eq!(mnemonic(inst), expected(inst), "{:#010x}", inst);
+    }
+}